@@ -0,0 +1,465 @@
+use crate::app::cores::config::{self, Config};
+use crate::app::cores::depen_manager::{self, Depen};
+use crate::app::cores::library::{LibraryLyrics, SharedReport};
+use crate::app::cores::lyrics_provider::DEFAULT_DURATION_TOLERANCE;
+use crate::app::cores::process::{ProcessHandle, SharedProcess};
+use crate::app::cores::progress::{Phase, SharedProgress};
+use crate::app::cores::translate::{TranslationMode, Translator};
+use crate::app::cores::url_checker::remove_radio;
+use crate::app::cores::ytdlp;
+use crate::app::ffmpeg::{Convert, FFmpegCompression};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::io::{self, Write};
+use std::thread;
+use std::time::Duration;
+
+const HELP: &str = "Usage: azul-box --cli <command> [options]
+
+Commands:
+  music <link>      Download music with cover, metadata and lyrics
+  video <link>      Download video
+  convert <file>    Convert a media file with ffmpeg, or a subtitle between srt, vtt, ass and lrc
+  lyrics <dir>      Add lyrics to the music files of a folder and its subfolders
+  update            Install or update yt-dlp, deno and ffmpeg
+
+Music options:
+  --dir <path>          Output directory
+  --format <name>       opus, flac, mp3, m4a or wav
+  --lang <code>         Lyrics language
+  --[no-]lyrics         YouTube lyrics
+  --[no-]auto-sub       Use YouTube auto generated subtitle
+  --[no-]sanitize       Sanitize YouTube lyrics
+  --[no-]lrclib         Lrclib lyrics
+  --[no-]kugou          Kugou lyrics
+  --[no-]word-synced    Word-synced Kugou lyrics for karaoke players
+  --translation <mode>  off, replace, interleave or sidecar
+  --lyrics-order <list> Lyrics sources to try in order, like kugou,lrclib,youtube
+  --tolerance <secs>    Skip lyrics timed for a track this much longer or shorter
+  --[no-]keep-lrc       Save a lrc file next to the music
+  --[no-]srt            Save synced lyrics as a srt file next to the music
+  --[no-]musicbrainz    Musicbrainz metadata
+  --threshold <0-100>   Musicbrainz similarity threshold
+  --[no-]acoustid       Identify tracks by fingerprint first, needs an AcoustID key in the config
+  --[no-]crop-cover     Crop cover to 1:1
+  --[no-]playlist-cover Use the playlist cover
+
+Video options:
+  --dir <path>          Output directory
+  --format <name>       mkv or mp4
+  --res <height>        Maximum resolution
+  --[no-]sub            Embed subtitles
+  --lang <code>         Subtitle language
+  --[no-]auto-sub       Use YouTube auto generated subtitle
+  --[no-]srt            Also save the subtitles as srt files next to the video
+  --[no-]translate-sub  Machine translate another subtitle when the language is missing
+
+Shared download options:
+  --fragments <1-10>    Concurrent fragments
+  --cookies <file>      cookies.txt to use
+  --[no-]radio          Keep the radio part of the link
+
+Lyrics options:
+  --lang, --lrclib, --kugou, --word-synced, --translation, --lyrics-order and
+  --tolerance work as for music
+  --[no-]dry-run        Only report what would be found
+  --[no-]lrc-only       Write .lrc files next to the music, leave the tags alone
+
+Convert options:
+  --to <ext>            Output format
+  --dir <path>          Output directory (default: next to the input)
+  --x264 <crf>          libx264 compression
+  --bitrate <kbit>      Audio bitrate
+  --jpg <level>         Jpg compression level
+  --png <level>         Png compression level
+
+Defaults come from the config file.";
+
+static VALUE_OPTIONS: &[&str] = &[
+    "dir",
+    "format",
+    "lang",
+    "threshold",
+    "fragments",
+    "cookies",
+    "lyrics-order",
+    "tolerance",
+    "translation",
+    "res",
+    "to",
+    "x264",
+    "bitrate",
+    "jpg",
+    "png",
+];
+
+pub fn is_cli(args: &[String]) -> bool {
+    args.first().is_some_and(|arg| arg == "--cli")
+}
+
+/// Run the command line front end and return the process exit code
+pub fn run(args: &[String]) -> i32 {
+    let args = match Args::parse(&args[1..]) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{e}\n\n{HELP}");
+            return 2;
+        }
+    };
+    config::config_file_default();
+    let configs = match config::load_config(&config::get_config_file_path()) {
+        Ok(config) => config,
+        Err(e) => {
+            log::error!("Fail to read config {e}");
+            Config::default()
+        }
+    };
+    let depen = depen_manager::get_path();
+
+    let result = match args.command.as_str() {
+        "music" => music(&args, &configs, &depen),
+        "video" => video(&args, &configs, &depen),
+        "convert" => convert(&args, &depen),
+        "lyrics" => library(&args, &configs),
+        "update" => update(&depen),
+        "help" | "--help" | "-h" => {
+            println!("{HELP}");
+            return 0;
+        }
+        other => {
+            eprintln!("Unknown command: {other}\n\n{HELP}");
+            return 2;
+        }
+    };
+    match result {
+        Ok(message) => {
+            println!("{message}");
+            0
+        }
+        Err(e) => {
+            log::error!("cli {}: {e}", args.command);
+            eprintln!("Fail: {e}");
+            1
+        }
+    }
+}
+
+struct Args {
+    command: String,
+    positional: Vec<String>,
+    options: HashMap<String, String>,
+    flags: HashMap<String, bool>,
+}
+
+impl Args {
+    fn parse(raw: &[String]) -> Result<Args, Box<dyn Error>> {
+        let mut iter = raw.iter();
+        let command = iter.next().ok_or("Missing command")?.clone();
+        let mut args = Args {
+            command,
+            positional: vec![],
+            options: HashMap::new(),
+            flags: HashMap::new(),
+        };
+        while let Some(arg) = iter.next() {
+            if let Some(name) = arg.strip_prefix("--") {
+                if VALUE_OPTIONS.contains(&name) {
+                    let value = iter.next().ok_or(format!("Missing value for --{name}"))?;
+                    args.options.insert(name.to_string(), value.clone());
+                } else if let Some(name) = name.strip_prefix("no-") {
+                    args.flags.insert(name.to_string(), false);
+                } else {
+                    args.flags.insert(name.to_string(), true);
+                }
+            } else {
+                args.positional.push(arg.clone());
+            }
+        }
+        Ok(args)
+    }
+    fn flag(&self, name: &str, default: bool) -> bool {
+        self.flags.get(name).copied().unwrap_or(default)
+    }
+    fn value<T: std::str::FromStr>(&self, name: &str, default: T) -> Result<T, Box<dyn Error>> {
+        match self.options.get(name) {
+            Some(value) => value
+                .parse::<T>()
+                .map_err(|_| format!("Invalid value for --{name}: {value}").into()),
+            None => Ok(default),
+        }
+    }
+    fn link(&self) -> Result<String, Box<dyn Error>> {
+        let link = self.positional.first().ok_or("Missing link")?;
+        if self.flag("radio", false) {
+            Ok(link.clone())
+        } else {
+            Ok(remove_radio(link))
+        }
+    }
+}
+
+fn translation_mode(
+    args: &Args,
+    default: Option<TranslationMode>,
+) -> Result<TranslationMode, Box<dyn Error>> {
+    match args.options.get("translation").map(|t| t.as_str()) {
+        Some("off") => Ok(TranslationMode::Off),
+        Some("replace") => Ok(TranslationMode::Replace),
+        Some("interleave") => Ok(TranslationMode::Interleave),
+        Some("sidecar") => Ok(TranslationMode::Sidecar),
+        Some(other) => Err(format!("Unknown translation mode: {other}").into()),
+        None => Ok(default.unwrap_or_default()),
+    }
+}
+
+fn check_yt_dlp(depen: &Depen) -> Result<(), Box<dyn Error>> {
+    if ytdlp::version_check(depen).is_none() {
+        return Err("yt-dlp is missing, run `azul-box --cli update` first".into());
+    }
+    Ok(())
+}
+
+fn music(args: &Args, configs: &Config, depen: &Depen) -> Result<String, Box<dyn Error>> {
+    check_yt_dlp(depen)?;
+    let music_dl = &configs.music_dl;
+    let format = match args.options.get("format").map(|f| f.as_str()) {
+        Some("opus") => 1,
+        Some("flac") => 2,
+        Some("mp3") => 3,
+        Some("m4a") => 4,
+        Some("wav") => 5,
+        Some(other) => return Err(format!("Unknown music format: {other}").into()),
+        None => music_dl.format.unwrap_or(1),
+    };
+    let directory = match args.options.get("dir") {
+        Some(dir) => dir.clone(),
+        None => dirs::audio_dir()
+            .map(|path| path.to_string_lossy().into_owned())
+            .ok_or("No output directory, use --dir")?,
+    };
+    let lyrics = args.flag("lyrics", music_dl.lyrics.unwrap_or(true));
+    let link = args.link()?;
+    let yt = ytdlp::Music {
+        link: link.clone(),
+        directory,
+        format,
+        lyrics,
+        frags: args.value("fragments", music_dl.fragments.unwrap_or(1))?,
+        lang_code: args.value(
+            "lang",
+            configs.universal.language.clone().unwrap_or("en".into()),
+        )?,
+        lyric_auto: args.flag("auto-sub", music_dl.auto_gen_sub.unwrap_or(false)),
+        sim_rate: args.value("threshold", music_dl.threshold.unwrap_or(90))?,
+        musicbrainz: args.flag("musicbrainz", music_dl.musicbrainz.unwrap_or(false)),
+        lrclib: args.flag("lrclib", music_dl.liblrc.unwrap_or(false)),
+        kugou_lyrics: args.flag("kugou", music_dl.kugou_lyrics.unwrap_or(false)),
+        cookies: args
+            .options
+            .get("cookies")
+            .cloned()
+            .or(configs.universal.cookies.clone()),
+        use_cookies: args.options.contains_key("cookies")
+            || configs.universal.use_cookies.unwrap_or(false),
+        crop_cover: args.flag("crop-cover", music_dl.crop_cover.unwrap_or(true)),
+        use_playlist_cover: args.flag(
+            "playlist-cover",
+            music_dl.use_playlist_cover.unwrap_or(true),
+        ),
+        sanitize_lyrics: args.flag("sanitize", false),
+        yt_dlp: depen.yt_dlp.clone(),
+        keep_lrc: args.flag("keep-lrc", music_dl.keep_lrc.unwrap_or(false)),
+        write_srt: args.flag("srt", music_dl.write_srt.unwrap_or(false)),
+        acoustid: args.flag("acoustid", music_dl.acoustid.unwrap_or(false)),
+        acoustid_key: music_dl.acoustid_key.clone().unwrap_or_default(),
+        acoustid_url: music_dl.acoustid_url.clone().unwrap_or_default(),
+        // Nobody is there to pick a match
+        review: false,
+        review_state: Default::default(),
+        translation: translation_mode(args, music_dl.translation)?,
+        translator: Translator::from_config(configs),
+        word_synced: args.flag("word-synced", music_dl.word_synced.unwrap_or(false)),
+        lyrics_order: match args.options.get("lyrics-order") {
+            Some(order) => order.split(',').map(|s| s.trim().to_string()).collect(),
+            None => music_dl.lyrics_order.clone().unwrap_or_default(),
+        },
+        duration_tolerance: args.value(
+            "tolerance",
+            music_dl
+                .duration_tolerance
+                .unwrap_or(DEFAULT_DURATION_TOLERANCE),
+        )?,
+    };
+    println!("Downloading music: {link}");
+    let files = with_progress(move |progress, process| yt.download(progress, process))?;
+    Ok(format!("Done:{files}"))
+}
+
+fn video(args: &Args, configs: &Config, depen: &Depen) -> Result<String, Box<dyn Error>> {
+    check_yt_dlp(depen)?;
+    let video_dl = &configs.video_dl;
+    let format = match args.options.get("format").map(|f| f.as_str()) {
+        Some("mkv") => 1,
+        Some("mp4") => 2,
+        Some(other) => return Err(format!("Unknown video format: {other}").into()),
+        None => video_dl.format.unwrap_or(1),
+    };
+    let directory = match args.options.get("dir") {
+        Some(dir) => dir.clone(),
+        None => dirs::video_dir()
+            .map(|path| path.to_string_lossy().into_owned())
+            .ok_or("No output directory, use --dir")?,
+    };
+    let lang: String = args.value(
+        "lang",
+        configs.universal.language.clone().unwrap_or("en".into()),
+    )?;
+    let link = args.link()?;
+    println!("Downloading video: {link}");
+    let video = ytdlp::Video {
+        link,
+        directory,
+        format,
+        frags: args.value("fragments", video_dl.fragments.unwrap_or(1))?,
+        sub: args.flag("sub", video_dl.subtitle.unwrap_or(true)),
+        lang,
+        auto_gen: args.flag("auto-sub", video_dl.auto_gen_sub.unwrap_or(false)),
+        cookies: args
+            .options
+            .get("cookies")
+            .cloned()
+            .or(configs.universal.cookies.clone()),
+        use_cookies: args.options.contains_key("cookies")
+            || configs.universal.use_cookies.unwrap_or(false),
+        res: args.value("res", video_dl.resolution.unwrap_or(1080))?,
+        yt_dlp: depen.yt_dlp.clone(),
+        write_srt: args.flag("srt", video_dl.write_srt.unwrap_or(false)),
+        ffmpeg: depen.ffmpeg.clone(),
+        translate_sub: args.flag("translate-sub", video_dl.translate_sub.unwrap_or(true)),
+        translator: Translator::from_config(configs),
+    };
+    let file = with_progress(move |progress, process| video.download(progress, process))?;
+    Ok(format!("Done: {file}"))
+}
+
+fn library(args: &Args, configs: &Config) -> Result<String, Box<dyn Error>> {
+    let music_dl = &configs.music_dl;
+    let directory = args.positional.first().ok_or("Missing directory")?;
+    let report = SharedReport::default();
+    let library = LibraryLyrics {
+        directory: directory.clone(),
+        lyrics_order: match args.options.get("lyrics-order") {
+            Some(order) => order.split(',').map(|s| s.trim().to_string()).collect(),
+            None => music_dl.lyrics_order.clone().unwrap_or_default(),
+        },
+        lrclib: args.flag("lrclib", music_dl.liblrc.unwrap_or(false)),
+        kugou_lyrics: args.flag("kugou", music_dl.kugou_lyrics.unwrap_or(false)),
+        word_synced: args.flag("word-synced", music_dl.word_synced.unwrap_or(false)),
+        lang_code: args.value(
+            "lang",
+            configs.universal.language.clone().unwrap_or("en".into()),
+        )?,
+        translation: translation_mode(args, music_dl.translation)?,
+        translator: Translator::from_config(configs),
+        duration_tolerance: args.value(
+            "tolerance",
+            music_dl
+                .duration_tolerance
+                .unwrap_or(DEFAULT_DURATION_TOLERANCE),
+        )?,
+        dry_run: args.flag("dry-run", false),
+        sidecar_only: args.flag("lrc-only", false),
+        report: report.clone(),
+    };
+    println!("Looking for lyrics in: {directory}");
+    let summary = with_progress(move |progress, process| library.run(progress, process))?;
+    for entry in report.lock().unwrap().iter() {
+        println!("{}: {}", entry.file.display(), entry.outcome);
+    }
+    Ok(summary)
+}
+
+fn convert(args: &Args, depen: &Depen) -> Result<String, Box<dyn Error>> {
+    let input = args.positional.first().ok_or("Missing input file")?;
+    let input_path = fs::canonicalize(input)?;
+    let format_out = args.options.get("to").ok_or("Missing --to <ext>")?;
+    let directory = match args.options.get("dir") {
+        Some(dir) => dir.clone(),
+        None => input_path
+            .parent()
+            .map(|p| p.to_string_lossy().into_owned())
+            .ok_or("Fail to find the input directory")?,
+    };
+    let compression = if args.options.contains_key("x264") {
+        FFmpegCompression::Libx264(args.value("x264", 23)?)
+    } else if args.options.contains_key("bitrate") {
+        FFmpegCompression::AudioBitrate(args.value("bitrate", 128)?)
+    } else if args.options.contains_key("jpg") {
+        FFmpegCompression::JpgCompression(args.value("jpg", 3)?)
+    } else if args.options.contains_key("png") {
+        FFmpegCompression::PngCompression(args.value("png", 100)?)
+    } else {
+        FFmpegCompression::None
+    };
+    println!("Converting: {input}");
+    let convert = Convert {
+        input: input_path.to_string_lossy().into_owned(),
+        directory,
+        format_out: format_out.clone(),
+        ffmpeg: depen.ffmpeg.clone(),
+        compression,
+    };
+    with_progress(move |_, process| convert.run(process))?;
+    Ok(format!("Done: {input}"))
+}
+
+/// Run a job on a worker thread and print its progress until it ends.
+/// Ctrl+C cancels the job so its child process does not outlive us
+fn with_progress<F>(job: F) -> Result<String, Box<dyn Error>>
+where
+    F: FnOnce(&SharedProgress, &ProcessHandle) -> Result<String, Box<dyn Error>> + Send + 'static,
+{
+    let progress = SharedProgress::default();
+    let process = SharedProcess::default();
+
+    let ctrl_c = process.clone();
+    tokio::task::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            eprintln!("\nCancelling");
+            ctrl_c.cancel();
+        }
+    });
+
+    let worker_progress = progress.clone();
+    let worker_process = process.clone();
+    let worker =
+        thread::spawn(move || job(&worker_progress, &worker_process).map_err(|e| e.to_string()));
+
+    let mut last_line = String::new();
+    while !worker.is_finished() {
+        let current = progress.lock().unwrap().clone();
+        let line = current.to_string();
+        if current.phase != Phase::Waiting && line != last_line {
+            let mut stderr = io::stderr();
+            let _ = write!(stderr, "\r\x1b[2K{line}");
+            let _ = stderr.flush();
+            last_line = line;
+        }
+        thread::sleep(Duration::from_millis(250));
+    }
+    if !last_line.is_empty() {
+        eprintln!();
+    }
+    match worker.join() {
+        Ok(result) => result.map_err(|e| e.into()),
+        Err(_) => Err("Download thread panicked".into()),
+    }
+}
+
+fn update(depen: &Depen) -> Result<String, Box<dyn Error>> {
+    println!("Updating dependencies in {:?}", depen.app_data);
+    let _ = fs::create_dir_all(&depen.app_data);
+    depen_manager::install(&depen.app_data)?;
+    Ok("Updated dependencies".to_string())
+}
@@ -0,0 +1,134 @@
+use lofty::prelude::*;
+use lofty::probe::Probe;
+use serde::Deserialize;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+use ureq::Agent;
+
+use crate::app::cores::depen_manager;
+
+/// Seconds of audio fingerprinted, what `fpcalc` reads by default
+const FINGERPRINT_LENGTH: u32 = 120;
+/// AcoustID results below this are other songs more often than not
+const MIN_SCORE: f32 = 0.5;
+
+/// A MusicBrainz recording AcoustID found for the fingerprint
+#[derive(Debug, Clone)]
+pub struct Match {
+    pub recording_id: String,
+    /// 0.0 to 1.0
+    pub score: f32,
+}
+
+pub struct Acoustid {
+    pub key: String,
+    pub url: String,
+    pub ffmpeg: PathBuf,
+}
+
+impl Acoustid {
+    /// None while no key is set
+    pub fn new(key: &str, url: &str) -> Option<Self> {
+        if key.is_empty() {
+            log::error!("No AcoustID api key set, searching by title");
+            return None;
+        }
+        Some(Self {
+            key: key.to_string(),
+            url: url.to_string(),
+            ffmpeg: depen_manager::get_path().ffmpeg.unwrap_or("ffmpeg".into()),
+        })
+    }
+
+    /// Recordings matching the sound of `file`, best first
+    pub fn identify(&self, file: &Path) -> Result<Vec<Match>, Box<dyn Error>> {
+        let duration = Probe::open(file)?.read()?.properties().duration().as_secs();
+        let fingerprint = self.fingerprint(file)?;
+        let url = format!("{}/v2/lookup", self.url.trim_end_matches('/'));
+        log::info!("AcoustID lookup: {url}");
+        // Errors come back as json with a 400, read them too
+        let agent: Agent = Agent::config_builder()
+            .http_status_as_error(false)
+            .timeout_global(Some(Duration::from_secs(10)))
+            .build()
+            .into();
+        // POST keeps the fingerprint out of the url
+        let response = agent
+            .post(&url)
+            .send_form([
+                ("client", self.key.as_str()),
+                ("format", "json"),
+                ("meta", "recordingids"),
+                ("duration", &duration.to_string()),
+                ("fingerprint", &fingerprint),
+            ])?
+            .body_mut()
+            .read_json::<LookupResponse>()?;
+        if let Some(error) = response.error {
+            return Err(format!("AcoustID: {}", error.message).into());
+        }
+
+        let mut matches: Vec<Match> = vec![];
+        for result in response.results.unwrap_or_default() {
+            if result.score < MIN_SCORE {
+                continue;
+            }
+            for recording in result.recordings.unwrap_or_default() {
+                if !matches
+                    .iter()
+                    .any(|found| found.recording_id == recording.id)
+                {
+                    matches.push(Match {
+                        recording_id: recording.id,
+                        score: result.score,
+                    });
+                }
+            }
+        }
+        matches.sort_by(|a, b| b.score.total_cmp(&a.score));
+        log::info!("{} recordings from AcoustID", matches.len());
+        Ok(matches)
+    }
+
+    /// Chromaprint fingerprint from the `chromaprint` muxer of ffmpeg, base64 like `fpcalc`
+    fn fingerprint(&self, file: &Path) -> Result<String, Box<dyn Error>> {
+        let output = Command::new(&self.ffmpeg)
+            .args(["-hide_banner", "-loglevel", "error", "-i"])
+            .arg(file)
+            .args(["-t", &FINGERPRINT_LENGTH.to_string()])
+            .args(["-f", "chromaprint", "-fp_format", "base64", "-"])
+            .output()?;
+        if !output.status.success() {
+            log::error!("{}", String::from_utf8_lossy(&output.stderr));
+            return Err(
+                "ffmpeg could not fingerprint the music, is it built with chromaprint?".into(),
+            );
+        }
+        let fingerprint = String::from_utf8(output.stdout)?.trim().to_string();
+        if fingerprint.is_empty() {
+            return Err("Empty fingerprint".into());
+        }
+        Ok(fingerprint)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct LookupResponse {
+    results: Option<Vec<LookupResult>>,
+    error: Option<LookupError>,
+}
+#[derive(Debug, Deserialize)]
+struct LookupResult {
+    score: f32,
+    recordings: Option<Vec<LookupRecording>>,
+}
+#[derive(Debug, Deserialize)]
+struct LookupRecording {
+    id: String,
+}
+#[derive(Debug, Deserialize)]
+struct LookupError {
+    message: String,
+}
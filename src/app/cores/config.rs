@@ -1,3 +1,5 @@
+use crate::app::cores::lyrics_provider::{DEFAULT_DURATION_TOLERANCE, DEFAULT_ORDER};
+use crate::app::cores::translate::{TranslationBackend, TranslationMode};
 use serde::{Deserialize, Serialize};
 use std::{
     fs,
@@ -81,6 +83,10 @@ pub struct Universal {
     pub use_cookies: Option<bool>,
     pub cookies: Option<String>,
     pub faq: Option<bool>,
+    pub concurrency: Option<u8>,
+    pub translator: Option<TranslationBackend>,
+    pub libretranslate_url: Option<String>,
+    pub libretranslate_key: Option<String>,
 }
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct VideoDl {
@@ -90,6 +96,8 @@ pub struct VideoDl {
     pub fragments: Option<i8>,
     pub resolution: Option<i32>,
     pub disable_radio: Option<bool>,
+    pub write_srt: Option<bool>,
+    pub translate_sub: Option<bool>,
 }
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct MusicDl {
@@ -105,6 +113,15 @@ pub struct MusicDl {
     pub use_playlist_cover: Option<bool>,
     pub disable_radio: Option<bool>,
     pub keep_lrc: Option<bool>,
+    pub lyrics_order: Option<Vec<String>>,
+    pub word_synced: Option<bool>,
+    pub translation: Option<TranslationMode>,
+    pub duration_tolerance: Option<u32>,
+    pub write_srt: Option<bool>,
+    pub musicbrainz_review: Option<bool>,
+    pub acoustid: Option<bool>,
+    pub acoustid_key: Option<String>,
+    pub acoustid_url: Option<String>,
 }
 impl Default for Config {
     fn default() -> Self {
@@ -114,6 +131,10 @@ impl Default for Config {
                 use_cookies: Some(false),
                 cookies: None,
                 faq: None,
+                concurrency: Some(2),
+                translator: Some(TranslationBackend::Google),
+                libretranslate_url: Some("http://localhost:5000".to_string()),
+                libretranslate_key: None,
             },
             video_dl: VideoDl {
                 format: Some(1),
@@ -122,6 +143,8 @@ impl Default for Config {
                 fragments: Some(1),
                 resolution: Some(1080),
                 disable_radio: Some(true),
+                write_srt: Some(false),
+                translate_sub: Some(true),
             },
             music_dl: MusicDl {
                 format: Some(1),
@@ -136,6 +159,20 @@ impl Default for Config {
                 use_playlist_cover: Some(true),
                 disable_radio: Some(true),
                 keep_lrc: Some(false),
+                lyrics_order: Some(
+                    DEFAULT_ORDER
+                        .iter()
+                        .map(|source| source.to_string())
+                        .collect(),
+                ),
+                word_synced: Some(false),
+                translation: Some(TranslationMode::Replace),
+                duration_tolerance: Some(DEFAULT_DURATION_TOLERANCE),
+                write_srt: Some(false),
+                musicbrainz_review: Some(false),
+                acoustid: Some(false),
+                acoustid_key: None,
+                acoustid_url: Some("https://api.acoustid.org".to_string()),
             },
         }
     }
@@ -149,6 +186,15 @@ impl Config {
 
         self.universal.use_cookies = self.universal.use_cookies.or(default.universal.use_cookies);
 
+        self.universal.concurrency = self.universal.concurrency.or(default.universal.concurrency);
+
+        self.universal.translator = self.universal.translator.or(default.universal.translator);
+
+        self.universal.libretranslate_url = self
+            .universal
+            .libretranslate_url
+            .or(default.universal.libretranslate_url);
+
         self.video_dl.format = self.video_dl.format.or(default.video_dl.format);
 
         self.video_dl.disable_radio = self
@@ -164,6 +210,13 @@ impl Config {
 
         self.video_dl.resolution = self.video_dl.resolution.or(default.video_dl.resolution);
 
+        self.video_dl.write_srt = self.video_dl.write_srt.or(default.video_dl.write_srt);
+
+        self.video_dl.translate_sub = self
+            .video_dl
+            .translate_sub
+            .or(default.video_dl.translate_sub);
+
         self.music_dl.format = self.music_dl.format.or(default.music_dl.format);
 
         self.music_dl.lyrics = self.music_dl.lyrics.or(default.music_dl.lyrics);
@@ -194,6 +247,28 @@ impl Config {
 
         self.music_dl.keep_lrc = self.music_dl.keep_lrc.or(default.music_dl.keep_lrc);
 
+        self.music_dl.lyrics_order = self.music_dl.lyrics_order.or(default.music_dl.lyrics_order);
+
+        self.music_dl.word_synced = self.music_dl.word_synced.or(default.music_dl.word_synced);
+
+        self.music_dl.translation = self.music_dl.translation.or(default.music_dl.translation);
+
+        self.music_dl.duration_tolerance = self
+            .music_dl
+            .duration_tolerance
+            .or(default.music_dl.duration_tolerance);
+
+        self.music_dl.write_srt = self.music_dl.write_srt.or(default.music_dl.write_srt);
+
+        self.music_dl.musicbrainz_review = self
+            .music_dl
+            .musicbrainz_review
+            .or(default.music_dl.musicbrainz_review);
+
+        self.music_dl.acoustid = self.music_dl.acoustid.or(default.music_dl.acoustid);
+
+        self.music_dl.acoustid_url = self.music_dl.acoustid_url.or(default.music_dl.acoustid_url);
+
         self
     }
 }
@@ -205,3 +280,61 @@ pub fn get_log_path() -> PathBuf {
     }
     data
 }
+
+pub fn get_translate_cache_dir() -> PathBuf {
+    let cache = dirs::cache_dir().unwrap().join("azulbox").join("translate");
+    if !cache.exists() {
+        let _ = fs::create_dir_all(&cache);
+    }
+    cache
+}
+
+pub fn get_queue_path() -> PathBuf {
+    let data = dirs::data_local_dir().unwrap().join("azulbox");
+    if !data.exists() {
+        let _ = fs::create_dir_all(&data);
+    }
+    data.join("queue.json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repair_fills_missing_settings() {
+        // A config written by an older version, before most settings existed
+        let old: Config = toml::from_str(
+            "[universal]\nlanguage = \"ja\"\n[video_dl]\n[music_dl]\nthreshold = 70\n",
+        )
+        .unwrap();
+        let config = old.repair();
+        assert_eq!(config.universal.language.as_deref(), Some("ja"));
+        assert_eq!(
+            config.universal.translator,
+            Some(TranslationBackend::Google)
+        );
+        assert_eq!(config.video_dl.translate_sub, Some(true));
+        assert_eq!(config.music_dl.threshold, Some(70));
+        assert_eq!(
+            config.music_dl.duration_tolerance,
+            Some(DEFAULT_DURATION_TOLERANCE)
+        );
+        assert_eq!(config.music_dl.translation, Some(TranslationMode::Replace));
+        assert_eq!(
+            config.music_dl.lyrics_order,
+            Some(
+                DEFAULT_ORDER
+                    .iter()
+                    .map(|source| source.to_string())
+                    .collect()
+            )
+        );
+        assert_eq!(
+            config.music_dl.acoustid_url.as_deref(),
+            Some("https://api.acoustid.org")
+        );
+        // No default key, AcoustID stays off until one is set
+        assert_eq!(config.music_dl.acoustid_key, None);
+    }
+}
@@ -1,7 +1,7 @@
-use crate::app::cores::files::file_finder;
+use crate::app::cores::files::{change_ext, file_finder, writable_tag};
 
 use image::error::ImageError;
-use image::{GenericImageView, ImageReader};
+use image::{GenericImageView, ImageFormat, ImageReader};
 use std::path::Path;
 
 pub fn square_crop_to_bytes(path: &Path) -> Result<Vec<u8>, ImageError> {
@@ -64,27 +64,16 @@ use lofty::config::WriteOptions;
 use lofty::picture::{Picture, PictureType};
 use lofty::prelude::*;
 use lofty::probe::Probe;
-use lofty::tag::Tag;
 use std::io::{BufReader, Cursor};
 
 fn embed_img_internal(cover: &Vec<u8>, musicfile: &Path) -> Result<(), lofty::error::LoftyError> {
     let mut reader = BufReader::new(Cursor::new(cover));
     let mut tagged_file = Probe::open(musicfile)?.read()?;
 
-    let tag = match tagged_file.primary_tag_mut() {
-        Some(primary_tag) => primary_tag,
-        None => {
-            if let Some(first_tag) = tagged_file.first_tag_mut() {
-                first_tag
-            } else {
-                let tag_type = tagged_file.primary_tag_type();
-
-                log::error!("No tags found, creating a new tag of type `{tag_type:?}`");
-                tagged_file.insert_tag(Tag::new(tag_type));
-
-                tagged_file.primary_tag_mut().unwrap()
-            }
-        }
+    let Some(tag) = writable_tag(&mut tagged_file) else {
+        log::error!("Cover report: No tag to embed into");
+        write_sidecar(cover, musicfile);
+        return Ok(());
     };
     let mut picture = Picture::from_reader(&mut reader)?;
     picture.set_pic_type(PictureType::CoverFront);
@@ -95,6 +84,34 @@ fn embed_img_internal(cover: &Vec<u8>, musicfile: &Path) -> Result<(), lofty::er
         log::info!("Cover report: Embedded Success");
     } else {
         log::error!("Cover report: Embedded Fail");
+        write_sidecar(cover, musicfile);
     }
     Ok(())
 }
+
+/// Whether the tag of `musicfile` holds a front cover
+pub fn has_cover(musicfile: &Path) -> bool {
+    Probe::open(musicfile)
+        .and_then(|probe| probe.read())
+        .is_ok_and(|tagged_file| {
+            tagged_file.tags().iter().any(|tag| {
+                tag.pictures()
+                    .iter()
+                    .any(|picture| picture.pic_type() == PictureType::CoverFront)
+            })
+        })
+}
+
+/// Keep the cover next to the music as `.jpg` when the tag can not hold it
+pub fn write_sidecar(cover: &[u8], musicfile: &Path) {
+    let sidecar = change_ext(musicfile, "jpg");
+    let result = match image::guess_format(cover) {
+        Ok(ImageFormat::Jpeg) => std::fs::write(&sidecar, cover).map_err(ImageError::from),
+        _ => image::load_from_memory(cover)
+            .and_then(|img| img.to_rgb8().save_with_format(&sidecar, ImageFormat::Jpeg)),
+    };
+    match result {
+        Ok(_) => log::info!("Cover report: Written {sidecar:?}"),
+        Err(e) => log::error!("Cover report: Fail to write {sidecar:?}: {e}"),
+    }
+}
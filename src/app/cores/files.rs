@@ -1,5 +1,9 @@
+use lofty::file::{FileType, TaggedFile};
+use lofty::prelude::*;
+use lofty::tag::Tag;
 use std::fs;
-use std::path::PathBuf;
+use std::io;
+use std::path::{Path, PathBuf};
 
 pub fn change_ext<P: Into<PathBuf>>(path: P, new_ext: &str) -> PathBuf {
     let mut p = path.into();
@@ -27,19 +31,119 @@ pub fn file_finder(directory: &str, filename: &str, matchs: &[&str]) -> Option<P
     None
 }
 
-pub fn file_finder_no_name(directory: &str, matchs: &[&str]) -> Option<PathBuf> {
-    let elements = fs::read_dir(&directory).ok()?;
+/// The file yt-dlp says it is about to write, from one line of its stdout
+pub fn written_file(line: &str) -> Option<PathBuf> {
+    let name = if let Some((_, name)) = line.split_once("Destination: ") {
+        name
+    } else if line.starts_with("[info] Writing") {
+        line.split_once(" to: ")?.1
+    } else if line.starts_with("[Merger]") {
+        line.split('"').nth(1)?
+    } else {
+        return None;
+    };
+    Some(PathBuf::from(name.trim()))
+}
 
-    for item in elements {
-        let path = item.ok()?.path();
-        if path.is_file() {
-            if let Some(ext) = path.extension().and_then(|ext| ext.to_str()) {
-                if matchs.contains(&ext) {
-                    let good_file = Some(path);
-                    return good_file;
-                }
+/// Remove what a cancelled job left behind: the files it wrote, their `.part`/`.ytdl`/fragment
+/// leftovers, and the converted thumbnails or lyrics that share their name
+pub fn remove_partial(directory: &Path, written: &[PathBuf]) {
+    let mut targets: Vec<PathBuf> = vec![];
+    for file in written {
+        let file = directory.join(file);
+        if let Some(ext) = file.extension().and_then(|ext| ext.to_str())
+            && ["webp", "jpg", "jpeg", "png", "vtt"].contains(&ext)
+        {
+            for sibling in ["jpg", "png", "lrc"] {
+                targets.push(change_ext(&file, sibling));
             }
         }
+        targets.push(file);
+    }
+    let Ok(elements) = fs::read_dir(directory) else {
+        return;
+    };
+    for item in elements.flatten() {
+        let path = item.path();
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let is_target = targets.iter().any(|target| {
+            target
+                .file_name()
+                .and_then(|target| target.to_str())
+                .is_some_and(|target| name.starts_with(target))
+        });
+        if is_target && path.is_file() {
+            match fs::remove_file(&path) {
+                Ok(_) => log::info!("Removed partial file {path:?}"),
+                Err(e) => log::error!("Fail to remove partial file {path:?}: {e}"),
+            }
+        }
+    }
+}
+
+/// Make a hidden, job-private folder inside `directory` for yt-dlp to work in
+pub fn staging_dir(directory: &Path) -> io::Result<PathBuf> {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|time| time.as_nanos())
+        .unwrap_or_default();
+    let staging = directory.join(format!(".azulbox-staging-{}-{nanos}", std::process::id()));
+    fs::create_dir_all(&staging)?;
+    Ok(staging)
+}
+
+/// Move `file` into `directory`, adding " (1)", " (2)"... instead of overwriting a file
+/// that is already there
+pub fn move_unique(file: &Path, directory: &Path) -> io::Result<PathBuf> {
+    let stem = file
+        .file_stem()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    let ext = file.extension().and_then(|ext| ext.to_str());
+    let mut target = directory.join(file.file_name().unwrap_or_default());
+    let mut count = 1;
+    while target.exists() {
+        let name = match ext {
+            Some(ext) => format!("{stem} ({count}).{ext}"),
+            None => format!("{stem} ({count})"),
+        };
+        target = directory.join(name);
+        count += 1;
+    }
+    move_file(file, &target)?;
+    Ok(target)
+}
+
+pub fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    // Rename fails across file systems, fall back to copy
+    if fs::rename(from, to).is_err() {
+        fs::copy(from, to)?;
+        fs::remove_file(from)?;
+    }
+    Ok(())
+}
+
+/// The tag to write metadata, cover and lyrics into, created when the file has none.
+/// yt-dlp only gives WAV a RIFF INFO chunk, which holds neither covers nor lyrics,
+/// so WAV gets an ID3v2 tag starting from what RIFF INFO already has
+pub fn writable_tag(tagged_file: &mut TaggedFile) -> Option<&mut Tag> {
+    let riff_only = tagged_file.file_type() == FileType::Wav && tagged_file.primary_tag().is_none();
+    if riff_only || tagged_file.first_tag().is_none() {
+        let tag_type = tagged_file.primary_tag_type();
+        log::warn!("No usable tag found, creating a new tag of type `{tag_type:?}`");
+        let mut tag = Tag::new(tag_type);
+        if let Some(info) = tagged_file.first_tag() {
+            for item in info.items() {
+                tag.insert(item.clone());
+            }
+        }
+        tagged_file.insert_tag(tag);
+    }
+    if tagged_file.primary_tag().is_some() {
+        tagged_file.primary_tag_mut()
+    } else {
+        tagged_file.first_tag_mut()
     }
-    None
 }
@@ -1,86 +1,165 @@
-use crate::app::cores::files::change_ext;
-use crate::app::cores::translate::translate;
+use crate::app::cores::lrc;
+use crate::app::cores::lrc::{Lrc, LrcLine, Word};
+use crate::app::cores::lyrics_provider::{Candidate, LyricsProvider, Query, text_score};
 use base64::prelude::*;
-use lofty::tag::TagType;
-use lofty::{self, config::WriteOptions, prelude::*, probe::Probe, tag::Tag};
+use flate2::read::ZlibDecoder;
+use regex::Regex;
 use serde::Deserialize;
+use std::cmp::Ordering;
 use std::error::Error;
-use std::fs;
-use std::path::Path;
+use std::io::Read;
 
-pub fn get(musicfile: &Path, lang: &str, keep_lrc: bool) -> Result<(), Box<dyn Error>> {
-    let mut tagged_file = Probe::open(musicfile)?.read()?;
+/// How many search results get their lyrics fetched and compared
+const MAX_RESULTS: usize = 3;
 
-    let tag = match tagged_file.primary_tag_mut() {
-        Some(primary_tag) => primary_tag,
-        None => {
-            if let Some(first_tag) = tagged_file.first_tag_mut() {
-                first_tag
-            } else {
-                let tag_type = tagged_file.primary_tag_type();
-
-                log::info!("No tags found, creating a new tag of type `{tag_type:?}`");
-                tagged_file.insert_tag(Tag::new(tag_type));
+pub struct Kugou {
+    /// Ask for KRC and keep the per-word timing
+    pub word_synced: bool,
+}
 
-                tagged_file.primary_tag_mut().ok_or("Fail to open tag")?
-            }
-        }
-    };
-    let title = &tag.title().ok_or("Fail to open tag title")?;
-
-    let data = kugou_search(&title)?;
-
-    if let Some(info) = data.data.info {
-        if let Some(best_match) = info.first() {
-            let lyrics = kugou_get_lyrics(&best_match.hash, lang)?;
-            if !lyrics.is_empty() {
-                if keep_lrc {
-                    fs::write(change_ext(&musicfile, "lrc"), &lyrics)?;
-                    log::info!("Written lrc file from lrclib");
-                }
+impl LyricsProvider for Kugou {
+    fn name(&self) -> &'static str {
+        "kugou"
+    }
 
-                if tag.tag_type() == TagType::Id3v2 {
-                    tag.insert_text(ItemKey::UnsyncLyrics, lyrics);
-                } else {
-                    tag.insert_text(ItemKey::Lyrics, lyrics);
+    fn search(&self, query: &Query) -> Result<Vec<Candidate>, Box<dyn Error>> {
+        let data = kugou_search(&query.title)?;
+        // Only fetch lyrics for the results that can be the same recording, best first
+        let mut infos: Vec<DataInfo> = data
+            .data
+            .info
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|info| query.accepts(info.duration.map(|duration| duration as f64)))
+            .collect();
+        infos.sort_by(|a, b| {
+            let score = |info: &DataInfo| text_score(query, &info.songname, &info.singername);
+            score(b).partial_cmp(&score(a)).unwrap_or(Ordering::Equal)
+        });
+        let mut candidates = vec![];
+        for info in infos.iter().take(MAX_RESULTS) {
+            match kugou_get_lyrics(&info.hash, self.word_synced) {
+                Ok(lyrics) if !lyrics.is_empty() => {
+                    let (lyrics, synced) = lrc::normalize(&lyrics);
+                    candidates.push(Candidate {
+                        source: self.name(),
+                        synced,
+                        lyrics,
+                        lang: None,
+                        duration: info.duration.map(|duration| duration as f64),
+                        score: text_score(query, &info.songname, &info.singername),
+                    })
                 }
-                tag.save_to_path(musicfile, WriteOptions::default())?;
+                Ok(_) => {}
+                Err(e) => log::error!("Fail to get kugou lyrics {}: {e}", info.hash),
             }
         }
+        Ok(candidates)
     }
-    Ok(())
 }
 
-fn kugou_get_lyrics(songhash: &str, lang: &str) -> Result<String, Box<dyn Error>> {
+/// Key of the XOR layer of KRC files
+const KRC_KEY: [u8; 16] = [
+    64, 71, 97, 119, 94, 50, 116, 71, 81, 54, 49, 45, 206, 210, 110, 105,
+];
+
+fn kugou_get_lyrics(songhash: &str, word_synced: bool) -> Result<String, Box<dyn Error>> {
     let request = ureq::get(format!(
         "https://krcs.kugou.com/search?ver=1&man=yes&client=mobi&hash={songhash}"
     ))
     .call()?
     .body_mut()
     .read_json::<KugouGetData>()?;
-    if let Some(candidates) = request.candidates {
-        if let Some(first_candidate) = candidates.first() {
-            let request_lyrics = ureq::get(format!("https://krcs.kugou.com/download?ver=1&man=yes&client=pc&fmt=lrc&id={}&accesskey={}", first_candidate.id, first_candidate.accesskey))
-                .call()?
-                .body_mut()
-                .read_json::<Lyrics>()?;
-            let u8_lyric = BASE64_STANDARD.decode(request_lyrics.content)?;
-            let str_lyric = str::from_utf8(&u8_lyric)?;
-            let translated_lyrics = translate(lang, str_lyric)?;
-            return Ok(translated_lyrics);
+    if let Some(candidates) = request.candidates
+        && let Some(first_candidate) = candidates.first()
+    {
+        if word_synced {
+            match kugou_download(first_candidate, "krc").and_then(|krc| krc_to_lrc(&krc)) {
+                Ok(lyrics) => return Ok(lyrics),
+                Err(e) => log::error!("Fail to get krc, fall back to lrc: {e}"),
+            }
         }
+        let u8_lyric = kugou_download(first_candidate, "lrc")?;
+        let str_lyric = str::from_utf8(&u8_lyric)?;
+        return Ok(str_lyric.to_string());
     }
     log::error!("Nothing exist");
     Err("Nothing exist".into())
 }
 
+fn kugou_download(lyrics_id: &LyricsId, fmt: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let request_lyrics = ureq::get(format!(
+        "https://krcs.kugou.com/download?ver=1&man=yes&client=pc&fmt={fmt}&id={}&accesskey={}",
+        lyrics_id.id, lyrics_id.accesskey
+    ))
+    .call()?
+    .body_mut()
+    .read_json::<Lyrics>()?;
+    Ok(BASE64_STANDARD.decode(request_lyrics.content)?)
+}
+
+/// KRC is `krc1` followed by zlib data XORed with `KRC_KEY`
+fn krc_decode(data: &[u8]) -> Result<String, Box<dyn Error>> {
+    let data = data.strip_prefix(b"krc1").ok_or("Not a krc file")?;
+    let unmasked: Vec<u8> = data
+        .iter()
+        .enumerate()
+        .map(|(index, byte)| byte ^ KRC_KEY[index % KRC_KEY.len()])
+        .collect();
+    let mut text = String::new();
+    ZlibDecoder::new(unmasked.as_slice()).read_to_string(&mut text)?;
+    Ok(text.trim_start_matches('\u{feff}').to_string())
+}
+
+/// KRC lines are `[start,duration]<offset,duration,0>word...` with word offsets relative
+/// to the line, turn them into enhanced LRC
+fn krc_to_lrc(data: &[u8]) -> Result<String, Box<dyn Error>> {
+    let krc = krc_decode(data)?;
+    let line_regex = Regex::new(r"^\[(\d+),(\d+)\](.*)$").unwrap();
+    let word_regex = Regex::new(r"<(\d+),(\d+),\d+>([^<]*)").unwrap();
+    let mut lrc = Lrc::default();
+    for line in krc.lines() {
+        let line = line.trim();
+        let Some(caps) = line_regex.captures(line) else {
+            // Keep the ID tags, they go through the LRC parser
+            lrc.tags.extend(Lrc::parse(line).tags);
+            continue;
+        };
+        let start: u64 = caps[1].parse()?;
+        let duration: u64 = caps[2].parse()?;
+        let mut lrc_line = LrcLine::new(start, "");
+        for word in word_regex.captures_iter(&caps[3]) {
+            let offset: u64 = word[1].parse()?;
+            lrc_line.text.push_str(&word[3]);
+            lrc_line.words.push(Word {
+                time: start + offset,
+                text: word[3].to_string(),
+            });
+        }
+        // The end of the last word lets players fill the line until it is sung
+        lrc_line.words.push(Word {
+            time: start + duration,
+            text: String::new(),
+        });
+        lrc_line.text = lrc_line.text.trim().to_string();
+        lrc.lines.push(lrc_line);
+    }
+    // `language` holds base64 translations, not something a player can use
+    lrc.tags.retain(|(key, _)| key != "language" && key != "id");
+    if !lrc.is_synced() {
+        return Err("Empty krc".into());
+    }
+    lrc.lines.sort_by_key(|line| line.time);
+    Ok(lrc.to_string())
+}
+
 #[derive(Deserialize)]
 struct KugouGetData {
-    candidates: Option<Vec<Candidate>>,
+    candidates: Option<Vec<LyricsId>>,
 }
 
 #[derive(Deserialize)]
-struct Candidate {
+struct LyricsId {
     id: String,
     accesskey: String,
 }
@@ -115,4 +194,50 @@ struct SearchData {
 #[derive(Deserialize, Clone)]
 struct DataInfo {
     hash: String,
+    #[serde(default)]
+    songname: String,
+    #[serde(default)]
+    singername: String,
+    duration: Option<u64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::Compression;
+    use flate2::write::ZlibEncoder;
+    use std::io::Write;
+
+    fn krc_encode(text: &str) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+        encoder.write_all(text.as_bytes()).unwrap();
+        let masked = encoder
+            .finish()
+            .unwrap()
+            .iter()
+            .enumerate()
+            .map(|(index, byte)| byte ^ KRC_KEY[index % KRC_KEY.len()])
+            .collect::<Vec<u8>>();
+        [b"krc1".as_slice(), &masked].concat()
+    }
+
+    #[test]
+    fn krc_to_enhanced_lrc() {
+        let krc = krc_encode(
+            "\u{feff}[ar:Band]\n[language:eyJjb250ZW50Ijpbe319]\n[id:$00000000]\n\
+             [3000,1000]<0,400,0>Second\n\
+             [1000,1500]<0,500,0>Hello <500,700,0>world\n",
+        );
+        assert_eq!(
+            krc_to_lrc(&krc).unwrap(),
+            "[ar:Band]\n\
+             [00:01.00]<00:01.00>Hello <00:01.50>world<00:02.50>\n\
+             [00:03.00]<00:03.00>Second<00:04.00>"
+        );
+    }
+
+    #[test]
+    fn not_krc() {
+        assert!(krc_to_lrc(b"[00:01.00]plain lrc").is_err());
+    }
 }
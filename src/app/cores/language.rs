@@ -0,0 +1,236 @@
+use crate::app::cores::lrc::Lrc;
+
+/// Common short words, enough to tell apart the latin script languages lyrics are often in
+static STOP_WORDS: &[(&str, &[&str])] = &[
+    (
+        "en",
+        &[
+            "the", "and", "you", "i", "to", "me", "my", "is", "it", "of", "that", "your", "be",
+            "we", "on", "for", "with", "don't", "i'm", "all", "what", "when",
+        ],
+    ),
+    (
+        "es",
+        &[
+            "el", "la", "que", "y", "los", "te", "mi", "me", "no", "un", "una", "por", "con", "es",
+            "tu", "yo", "lo", "las", "pero", "como", "más",
+        ],
+    ),
+    (
+        "fr",
+        &[
+            "le", "la", "les", "et", "je", "tu", "que", "pas", "un", "une", "est", "pour", "dans",
+            "mon", "moi", "toi", "qui", "ne", "c'est", "j'ai", "nous", "vous",
+        ],
+    ),
+    (
+        "de",
+        &[
+            "der", "die", "das", "und", "ich", "du", "nicht", "ist", "ein", "eine", "mich", "mir",
+            "dich", "zu", "mit", "wir", "sie", "auf", "auch",
+        ],
+    ),
+    (
+        "pt",
+        &[
+            "o", "a", "que", "e", "não", "eu", "você", "meu", "minha", "um", "uma", "com", "para",
+            "se", "é", "do", "da", "em", "mais",
+        ],
+    ),
+    (
+        "it",
+        &[
+            "il", "di", "che", "e", "non", "io", "tu", "mi", "ti", "un", "una", "per", "sono", "è",
+            "con", "del", "della", "ma", "anche",
+        ],
+    ),
+    (
+        "nl",
+        &[
+            "de", "het", "een", "en", "ik", "je", "niet", "is", "van", "dat", "op", "mijn", "wij",
+            "met", "voor", "maar",
+        ],
+    ),
+    (
+        "id",
+        &[
+            "aku", "kau", "dan", "yang", "di", "tak", "tidak", "ini", "itu", "kamu", "dengan",
+            "untuk", "ke", "cinta", "akan",
+        ],
+    ),
+];
+
+#[derive(Default)]
+struct ScriptCount {
+    latin: usize,
+    vietnamese: usize,
+    kana: usize,
+    han: usize,
+    hangul: usize,
+    cyrillic: usize,
+    ukrainian: usize,
+    arabic: usize,
+    persian: usize,
+    hebrew: usize,
+    greek: usize,
+    thai: usize,
+    devanagari: usize,
+    bengali: usize,
+    tamil: usize,
+}
+
+/// Guess the language of lyrics from the scripts they use, and from common words for
+/// latin script. Gives up rather than guess wrong
+pub fn detect(lyrics: &str) -> Option<String> {
+    let lrc = Lrc::parse(lyrics);
+    let text = if lrc.is_synced() {
+        lrc.plain()
+    } else {
+        lyrics.to_string()
+    };
+
+    let mut count = ScriptCount::default();
+    for c in text.chars() {
+        match c as u32 {
+            0x41..=0x5A | 0x61..=0x7A | 0xC0..=0x24F => count.latin += 1,
+            0x1EA0..=0x1EFF => {
+                count.latin += 1;
+                count.vietnamese += 1;
+            }
+            0x3040..=0x30FF => count.kana += 1,
+            0x3400..=0x4DBF | 0x4E00..=0x9FFF => count.han += 1,
+            0x1100..=0x11FF | 0x3130..=0x318F | 0xAC00..=0xD7AF => count.hangul += 1,
+            0x0400..=0x04FF => {
+                count.cyrillic += 1;
+                if "іїєґІЇЄҐ".contains(c) {
+                    count.ukrainian += 1;
+                }
+            }
+            0x0600..=0x06FF => {
+                count.arabic += 1;
+                if "پچژگ".contains(c) {
+                    count.persian += 1;
+                }
+            }
+            0x0590..=0x05FF => count.hebrew += 1,
+            0x0370..=0x03FF => count.greek += 1,
+            0x0E00..=0x0E7F => count.thai += 1,
+            0x0900..=0x097F => count.devanagari += 1,
+            0x0980..=0x09FF => count.bengali += 1,
+            0x0B80..=0x0BFF => count.tamil += 1,
+            _ => {}
+        }
+    }
+
+    let cjk = count.kana + count.han;
+    let scripts = [
+        (count.latin, "latin"),
+        (cjk, "cjk"),
+        (count.hangul, "ko"),
+        (count.cyrillic, "cyrillic"),
+        (count.arabic, "arabic"),
+        (count.hebrew, "he"),
+        (count.greek, "el"),
+        (count.thai, "th"),
+        (count.devanagari, "hi"),
+        (count.bengali, "bn"),
+        (count.tamil, "ta"),
+    ];
+    let (letters, script) = scripts.into_iter().max_by_key(|(letters, _)| *letters)?;
+    if letters == 0 {
+        return None;
+    }
+    let lang = match script {
+        // Japanese mixes kanji with kana, Chinese never uses kana
+        "cjk" if count.kana * 10 >= cjk => "ja",
+        "cjk" => "zh",
+        "cyrillic" if count.ukrainian > 0 => "uk",
+        "cyrillic" => "ru",
+        "arabic" if count.persian > 0 => "fa",
+        "arabic" => "ar",
+        "latin" if count.vietnamese * 20 >= count.latin => "vi",
+        "latin" => return detect_latin(&text),
+        other => other,
+    };
+    Some(lang.to_string())
+}
+
+fn detect_latin(text: &str) -> Option<String> {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric() && c != '\'')
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect();
+    let mut scores: Vec<(usize, &str)> = STOP_WORDS
+        .iter()
+        .map(|(lang, stop_words)| {
+            let hits = words
+                .iter()
+                .filter(|word| stop_words.contains(&word.as_str()))
+                .count();
+            (hits, *lang)
+        })
+        .collect();
+    scores.sort_by_key(|(hits, _)| std::cmp::Reverse(*hits));
+    match scores.as_slice() {
+        [(best, lang), (second, _), ..] if *best >= 3 && best > second => Some(lang.to_string()),
+        _ => None,
+    }
+}
+
+/// `zh` and `zh-CN` are the same language for translation purposes
+pub fn same(a: &str, b: &str) -> bool {
+    let primary = |code: &str| {
+        code.split(['-', '_'])
+            .next()
+            .unwrap_or_default()
+            .to_lowercase()
+    };
+    primary(a) == primary(b)
+}
+
+/// ISO 639-2 code for the language tag, ID3v2 `TLAN` and most players expect three letters
+pub fn iso639_2(code: &str) -> Option<&'static str> {
+    let code = match code
+        .split(['-', '_'])
+        .next()
+        .unwrap_or_default()
+        .to_lowercase()
+        .as_str()
+    {
+        "en" => "eng",
+        "fr" => "fra",
+        "es" => "spa",
+        "zh" => "zho",
+        "de" => "deu",
+        "ja" => "jpn",
+        "ar" => "ara",
+        "ru" => "rus",
+        "it" => "ita",
+        "pt" => "por",
+        "nl" => "nld",
+        "sv" => "swe",
+        "no" => "nor",
+        "fi" => "fin",
+        "da" => "dan",
+        "pl" => "pol",
+        "cs" => "ces",
+        "hu" => "hun",
+        "ro" => "ron",
+        "tr" => "tur",
+        "vi" => "vie",
+        "ko" => "kor",
+        "el" => "ell",
+        "he" => "heb",
+        "th" => "tha",
+        "id" => "ind",
+        "ms" => "msa",
+        "hi" => "hin",
+        "uk" => "ukr",
+        "bn" => "ben",
+        "ta" => "tam",
+        "fa" => "fas",
+        _ => return None,
+    };
+    Some(code)
+}
@@ -0,0 +1,222 @@
+use crate::app::cores::files::change_ext;
+use crate::app::cores::kugou::Kugou;
+use crate::app::cores::lrclib::Lrclib;
+use crate::app::cores::lyrics;
+use crate::app::cores::lyrics_provider::{self, DEFAULT_DURATION_TOLERANCE, LyricsProvider, Query};
+use crate::app::cores::process::ProcessHandle;
+use crate::app::cores::progress::{Phase, Progress, SharedProgress};
+use crate::app::cores::translate::{TranslationMode, Translator};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Audio files lofty can read and write lyrics into
+pub const AUDIO_FORMAT: &[&str] = &["mp3", "flac", "opus", "ogg", "m4a", "wav"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Found { source: &'static str, synced: bool },
+    NotFound,
+    Skipped(String),
+    Failed(String),
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::Found { source, synced } => {
+                let kind = if *synced { "synced" } else { "plain" };
+                write!(f, "Found {kind} from {source}")
+            }
+            Outcome::NotFound => write!(f, "Not found"),
+            Outcome::Skipped(reason) => write!(f, "Skipped, {reason}"),
+            Outcome::Failed(e) => write!(f, "Failed, {e}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ReportEntry {
+    pub file: PathBuf,
+    pub outcome: Outcome,
+}
+
+pub type SharedReport = Arc<Mutex<Vec<ReportEntry>>>;
+
+/// Look up lyrics for music already on disk, the same way a download does
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LibraryLyrics {
+    pub directory: String,
+    pub lyrics_order: Vec<String>,
+    pub lrclib: bool,
+    pub kugou_lyrics: bool,
+    pub word_synced: bool,
+    pub lang_code: String,
+    pub translation: TranslationMode,
+    #[serde(default)]
+    pub translator: Translator,
+    #[serde(default = "default_duration_tolerance")]
+    pub duration_tolerance: u32,
+    /// Only report what would be found, nothing is written
+    pub dry_run: bool,
+    /// Write `.lrc` next to the music and leave the tags alone
+    pub sidecar_only: bool,
+    /// Filled while the job runs, read by the window
+    #[serde(skip)]
+    pub report: SharedReport,
+}
+
+fn default_duration_tolerance() -> u32 {
+    DEFAULT_DURATION_TOLERANCE
+}
+
+impl LibraryLyrics {
+    pub fn run(
+        self,
+        progress: &SharedProgress,
+        process: &ProcessHandle,
+    ) -> Result<String, Box<dyn Error>> {
+        let files = audio_files(Path::new(&self.directory));
+        if files.is_empty() {
+            return Err("No audio file found".into());
+        }
+        // YouTube lyrics only exist while downloading
+        let mut providers: Vec<Box<dyn LyricsProvider>> = vec![];
+        for source in &self.lyrics_order {
+            match source.as_str() {
+                "kugou" if self.kugou_lyrics => providers.push(Box::new(Kugou {
+                    word_synced: self.word_synced,
+                })),
+                "lrclib" if self.lrclib => providers.push(Box::new(Lrclib)),
+                _ => {}
+            }
+        }
+        if providers.is_empty() {
+            return Err("No lyrics source enabled, turn on kugou or lrclib".into());
+        }
+        self.report.lock().unwrap().clear();
+
+        let total = files.len() as u32;
+        let mut found = 0;
+        for (index, file) in files.into_iter().enumerate() {
+            process.check()?;
+            Progress::set_phase(progress, Phase::Lyrics);
+            if let Ok(mut progress) = progress.lock() {
+                progress.title = file.file_name().map(|name| name.to_string_lossy().into());
+                progress.playlist_index = Some(index as u32 + 1);
+                progress.playlist_count = Some(total);
+                progress.fraction = Some(index as f32 / total as f32);
+            }
+            let outcome = self.lyrics_for(&providers, &file);
+            log::info!("{file:?}: {outcome}");
+            if let Outcome::Found { .. } = outcome {
+                found += 1;
+            }
+            self.report
+                .lock()
+                .unwrap()
+                .push(ReportEntry { file, outcome });
+        }
+        Ok(format!("Lyrics found for {found} of {total} files"))
+    }
+
+    fn lyrics_for(&self, providers: &[Box<dyn LyricsProvider>], file: &Path) -> Outcome {
+        let lrc_file = change_ext(file, "lrc");
+        if self.sidecar_only && lrc_file.exists() {
+            return Outcome::Skipped("has a .lrc file".into());
+        }
+        match lyrics::has_lyrics(file) {
+            Ok(true) => return Outcome::Skipped("already has lyrics".into()),
+            Ok(false) => {}
+            Err(e) => return Outcome::Failed(e.to_string()),
+        }
+        let query = match Query::from_file(file, self.duration_tolerance) {
+            Ok(query) => query,
+            Err(e) => return Outcome::Failed(e.to_string()),
+        };
+        let Some(candidate) = lyrics_provider::find(providers, &query) else {
+            return Outcome::NotFound;
+        };
+        let outcome = Outcome::Found {
+            source: candidate.source,
+            synced: candidate.synced,
+        };
+        if self.dry_run {
+            return outcome;
+        }
+        let written = lyrics_provider::translate_candidate(
+            candidate,
+            file,
+            &self.lang_code,
+            self.translation,
+            &self.translator,
+        )
+        .and_then(|(lyrics, lang)| {
+            if self.sidecar_only {
+                fs::write(&lrc_file, lyrics)?;
+                Ok(())
+            } else {
+                lyrics::embed(file, &lyrics, lang.as_deref(), false)
+            }
+        });
+        match written {
+            Ok(_) => outcome,
+            Err(e) => Outcome::Failed(e.to_string()),
+        }
+    }
+}
+
+/// Every audio file under `directory`, sorted so the report follows the folders
+pub fn audio_files(directory: &Path) -> Vec<PathBuf> {
+    let mut files = vec![];
+    let mut folders = vec![directory.to_path_buf()];
+    while let Some(folder) = folders.pop() {
+        let entries = match fs::read_dir(&folder) {
+            Ok(entries) => entries,
+            Err(e) => {
+                log::error!("Fail to read {folder:?}: {e}");
+                continue;
+            }
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            // A linked folder can point back up the tree, it is not followed
+            if entry.file_type().is_ok_and(|kind| kind.is_dir()) {
+                folders.push(path);
+            } else if path.is_dir() {
+                continue;
+            } else if path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| AUDIO_FORMAT.contains(&ext.to_lowercase().as_str()))
+            {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn audio_files_skip_linked_folders() {
+        let root = std::env::temp_dir().join(format!("azulbox-library-{}", std::process::id()));
+        let album = root.join("album");
+        fs::create_dir_all(&album).unwrap();
+        fs::write(album.join("song.MP3"), b"").unwrap();
+        fs::write(album.join("cover.jpg"), b"").unwrap();
+        // Points back up the tree, following it would never end
+        std::os::unix::fs::symlink(&root, album.join("loop")).unwrap();
+        let files = audio_files(&root);
+        fs::remove_dir_all(&root).unwrap();
+        assert_eq!(files, [album.join("song.MP3")]);
+    }
+}
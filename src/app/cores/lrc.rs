@@ -0,0 +1,368 @@
+use std::fmt;
+
+/// One timed word of an enhanced LRC line, `<mm:ss.xx>word`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Word {
+    /// Milliseconds from the start of the track
+    pub time: u64,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LrcLine {
+    /// Milliseconds from the start of the track
+    pub time: u64,
+    pub text: String,
+    /// Empty unless the line has word-level timing
+    pub words: Vec<Word>,
+}
+
+impl LrcLine {
+    pub fn new(time: u64, text: &str) -> Self {
+        Self {
+            time,
+            text: text.to_string(),
+            words: vec![],
+        }
+    }
+}
+
+/// Parsed LRC lyrics. Lines with several timestamps are split into one line per timestamp
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Lrc {
+    /// ID tags like `ar`, `ti`, `al`, `length` or `offset`, in file order
+    pub tags: Vec<(String, String)>,
+    /// Sorted by time
+    pub lines: Vec<LrcLine>,
+}
+
+impl Lrc {
+    pub fn parse(text: &str) -> Self {
+        let mut lrc = Lrc::default();
+        for raw in text.lines() {
+            let mut rest = raw.trim();
+            let mut times = vec![];
+            let mut tags = vec![];
+            while let Some(inner) = rest.strip_prefix('[') {
+                let Some(end) = inner.find(']') else {
+                    break;
+                };
+                let bracket = &inner[..end];
+                if let Some(time) = parse_time(bracket) {
+                    times.push(time);
+                } else if let Some((key, value)) = bracket.split_once(':')
+                    && !key.is_empty()
+                    && key.chars().all(|c| c.is_ascii_alphabetic())
+                {
+                    tags.push((key.trim().to_lowercase(), value.trim().to_string()));
+                } else {
+                    break;
+                }
+                rest = &inner[end + 1..];
+            }
+            if times.is_empty() {
+                lrc.tags.extend(tags);
+                continue;
+            }
+            for time in times {
+                let (text, words) = parse_words(rest, time);
+                lrc.lines.push(LrcLine { time, text, words });
+            }
+        }
+        lrc.lines.sort_by_key(|line| line.time);
+        lrc
+    }
+
+    pub fn is_synced(&self) -> bool {
+        !self.lines.is_empty()
+    }
+
+    /// The lyrics without any timing
+    pub fn plain(&self) -> String {
+        self.lines
+            .iter()
+            .map(|line| line.text.as_str())
+            .collect::<Vec<&str>>()
+            .join("\n")
+    }
+
+    pub fn has_words(&self) -> bool {
+        self.lines.iter().any(|line| !line.words.is_empty())
+    }
+
+    pub fn tag(&self, key: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// `[offset:]` in milliseconds, a positive value shows the lyrics sooner
+    pub fn offset(&self) -> i64 {
+        self.tag("offset")
+            .and_then(|offset| offset.trim_start_matches('+').parse().ok())
+            .unwrap_or(0)
+    }
+
+    /// Move every line and word by `ms`, never before the start of the track
+    pub fn shift(&mut self, ms: i64) {
+        let move_time = |time: u64| time.saturating_add_signed(ms);
+        for line in self.lines.iter_mut() {
+            line.time = move_time(line.time);
+            for word in line.words.iter_mut() {
+                word.time = move_time(word.time);
+            }
+        }
+    }
+
+    /// Bake the `[offset:]` tag into the timestamps for players that ignore it
+    pub fn apply_offset(&mut self) {
+        self.shift(-self.offset());
+        self.tags.retain(|(key, _)| key != "offset");
+    }
+}
+
+impl fmt::Display for Lrc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut lines: Vec<String> = self
+            .tags
+            .iter()
+            .map(|(key, value)| format!("[{key}:{value}]"))
+            .collect();
+        for line in &self.lines {
+            if line.words.is_empty() {
+                lines.push(format!("[{}]{}", format_time(line.time), line.text));
+            } else {
+                let words: String = line
+                    .words
+                    .iter()
+                    .map(|word| format!("<{}>{}", format_time(word.time), word.text))
+                    .collect();
+                lines.push(format!("[{}]{words}", format_time(line.time)));
+            }
+        }
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+/// Tidy lyrics from any source, synced ones come back in one consistent shape
+pub fn normalize(lyrics: &str) -> (String, bool) {
+    let mut lrc = Lrc::parse(lyrics);
+    if lrc.is_synced() {
+        lrc.apply_offset();
+        (lrc.to_string(), true)
+    } else {
+        (lyrics.trim().to_string(), false)
+    }
+}
+
+/// `mm:ss.xx`, minutes go past 59 for long tracks since most players expect two fields
+pub fn format_time(ms: u64) -> String {
+    let centis = ms / 10;
+    format!(
+        "{:02}:{:02}.{:02}",
+        centis / 6000,
+        centis / 100 % 60,
+        centis % 100
+    )
+}
+
+/// Accept `mm:ss`, `mm:ss.xx`, `mm:ss.xxx`, `mm:ss:xx` and `h:mm:ss.xx`
+pub fn parse_time(time: &str) -> Option<u64> {
+    let parts: Vec<&str> = time.trim().split(':').collect();
+    let (hours, minutes, whole, fraction) = match parts.as_slice() {
+        [m, s] => {
+            let (whole, fraction) = s.split_once('.').unwrap_or((s, ""));
+            (0, *m, whole, fraction)
+        }
+        // Only a fraction tells hours apart from the `mm:ss:xx` some editors write
+        [h, m, s] if s.contains('.') => {
+            let (whole, fraction) = s.split_once('.')?;
+            (h.parse::<u64>().ok()?, *m, whole, fraction)
+        }
+        [m, s, centis] => (0, *m, *s, *centis),
+        _ => return None,
+    };
+    let minutes: u64 = minutes.parse().ok()?;
+    if whole.is_empty() || !whole.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    if !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let whole: u64 = whole.parse().ok()?;
+    // ".5" is half a second and ".05" fifty milliseconds
+    let millis = format!("{fraction:0<3}")[..3].parse::<u64>().ok()?;
+    // Digits come from downloaded lyrics and user files, too many of them is no time
+    hours
+        .checked_mul(60)?
+        .checked_add(minutes)?
+        .checked_mul(60)?
+        .checked_add(whole)?
+        .checked_mul(1000)?
+        .checked_add(millis)
+}
+
+/// Split `<mm:ss.xx>word <mm:ss.xx>word` into plain text and timed words,
+/// text before the first timestamp is a word starting with the line
+fn parse_words(text: &str, line_time: u64) -> (String, Vec<Word>) {
+    let mut words = vec![];
+    let mut plain = String::new();
+    let mut rest = text;
+    let mut current: Option<u64> = Some(line_time);
+    let mut stamped = false;
+    while let Some(start) = rest.find('<') {
+        let Some(end) = rest[start..].find('>').map(|end| start + end) else {
+            break;
+        };
+        let Some(time) = parse_time(&rest[start + 1..end]) else {
+            // Not a timestamp, keep the text as it is
+            plain.push_str(&rest[..=end]);
+            push_word(&mut words, current, &rest[..=end]);
+            rest = &rest[end + 1..];
+            continue;
+        };
+        plain.push_str(&rest[..start]);
+        if stamped || !rest[..start].trim().is_empty() {
+            push_word(&mut words, current, &rest[..start]);
+        }
+        current = Some(time);
+        stamped = true;
+        rest = &rest[end + 1..];
+    }
+    plain.push_str(rest);
+    if !stamped {
+        return (plain.trim().to_string(), vec![]);
+    }
+    if rest.is_empty() {
+        // A trailing timestamp marks when the last word ends
+        if let Some(time) = current
+            && words.last().is_none_or(|word| word.time != time)
+        {
+            words.push(Word {
+                time,
+                text: String::new(),
+            });
+        }
+    } else {
+        push_word(&mut words, current, rest);
+    }
+    (plain.trim().to_string(), words)
+}
+
+fn push_word(words: &mut Vec<Word>, time: Option<u64>, text: &str) {
+    if text.is_empty() {
+        return;
+    }
+    match (time, words.last_mut()) {
+        (Some(time), Some(last)) if last.time == time => last.text.push_str(text),
+        (Some(time), _) => words.push(Word {
+            time,
+            text: text.to_string(),
+        }),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_time_formats() {
+        assert_eq!(parse_time("01:02"), Some(62_000));
+        assert_eq!(parse_time("01:02.5"), Some(62_500));
+        assert_eq!(parse_time("01:02.05"), Some(62_050));
+        assert_eq!(parse_time("01:02.345"), Some(62_345));
+        assert_eq!(parse_time("1:01:02.00"), Some(3_662_000));
+        assert_eq!(parse_time("01:02:34"), Some(62_340));
+        assert_eq!(parse_time("ar:Someone"), None);
+        assert_eq!(parse_time("01:02.x"), None);
+        assert_eq!(
+            parse_time("99999999999999:00"),
+            Some(5_999_999_999_999_940_000)
+        );
+        assert_eq!(parse_time("999999999999999:00"), None);
+        assert_eq!(parse_time("99999999999999999999:00"), None);
+    }
+
+    #[test]
+    fn parse_lines_and_tags() {
+        let lrc = Lrc::parse("[ti:Song]\n[offset:+500]\n[00:05.00][00:01.00]Twice\n[00:03.00]Once");
+        assert_eq!(lrc.tag("ti"), Some("Song"));
+        assert_eq!(lrc.offset(), 500);
+        let lines: Vec<(u64, &str)> = lrc
+            .lines
+            .iter()
+            .map(|line| (line.time, line.text.as_str()))
+            .collect();
+        assert_eq!(lines, [(1000, "Twice"), (3000, "Once"), (5000, "Twice")]);
+    }
+
+    #[test]
+    fn apply_offset_moves_lines() {
+        let mut lrc = Lrc::parse("[offset:500]\n[00:00.20]First\n[00:02.00]Second");
+        lrc.apply_offset();
+        assert_eq!(lrc.tag("offset"), None);
+        assert_eq!(lrc.lines[0].time, 0);
+        assert_eq!(lrc.lines[1].time, 1500);
+    }
+
+    #[test]
+    fn parse_words_and_round_trip() {
+        let text = "[00:01.00]<00:01.00>Hello <00:01.50>world<00:02.00>";
+        let lrc = Lrc::parse(text);
+        let line = &lrc.lines[0];
+        assert_eq!(line.text, "Hello world");
+        assert_eq!(
+            line.words,
+            [
+                Word {
+                    time: 1000,
+                    text: "Hello ".into()
+                },
+                Word {
+                    time: 1500,
+                    text: "world".into()
+                },
+                Word {
+                    time: 2000,
+                    text: String::new()
+                },
+            ]
+        );
+        assert_eq!(lrc.to_string(), text);
+        assert_eq!(Lrc::parse(&lrc.to_string()), lrc);
+    }
+
+    #[test]
+    fn text_before_first_word_stamp_is_kept() {
+        let lrc = Lrc::parse("[00:01.00]Oh <00:01.50>yeah");
+        let line = &lrc.lines[0];
+        assert_eq!(line.text, "Oh yeah");
+        assert_eq!(line.words[0].time, 1000);
+        assert_eq!(line.words[0].text, "Oh ");
+        assert_eq!(line.words[1].time, 1500);
+        assert_eq!(Lrc::parse(&lrc.to_string()).lines[0].text, "Oh yeah");
+    }
+
+    #[test]
+    fn plain_line_has_no_words() {
+        let lrc = Lrc::parse("[00:01.00]a <b> c");
+        assert_eq!(lrc.lines[0].text, "a <b> c");
+        assert!(lrc.lines[0].words.is_empty());
+        assert!(!lrc.has_words());
+    }
+
+    #[test]
+    fn normalize_plain_lyrics() {
+        assert_eq!(
+            normalize("  just text \n"),
+            ("just text".to_string(), false)
+        );
+        assert_eq!(
+            normalize("[00:01]a\n[00:00.5]b"),
+            ("[00:00.50]b\n[00:01.00]a".to_string(), true)
+        );
+    }
+}
@@ -1,81 +1,68 @@
-use std::path::Path;
-use std::{error::Error, fs};
+use std::error::Error;
 use url::form_urlencoded;
 
-use lofty::{
-    self,
-    config::WriteOptions,
-    prelude::*,
-    probe::Probe,
-    tag::{Tag, TagType},
-};
 use serde::Deserialize;
 
-use crate::app::cores::files::change_ext;
-use crate::app::cores::{string_cleaner, translate::translate};
+use crate::app::cores::lyrics_provider::{Candidate, LyricsProvider, Query, text_score};
+use crate::app::cores::{lrc, string_cleaner};
 
-pub fn lrclib_fetch(musicfile: &Path, lang: &str, keep_lrc: bool) -> Result<(), Box<dyn Error>> {
-    let mut tagged_file = Probe::open(musicfile)?.read()?;
+pub struct Lrclib;
 
-    let tag = match tagged_file.primary_tag_mut() {
-        Some(primary_tag) => primary_tag,
-        None => {
-            if let Some(first_tag) = tagged_file.first_tag_mut() {
-                first_tag
-            } else {
-                let tag_type = tagged_file.primary_tag_type();
-
-                log::warn!("No tags found, creating a new tag of type `{tag_type:?}`");
-                tagged_file.insert_tag(Tag::new(tag_type));
-
-                tagged_file.primary_tag_mut().ok_or("Fail to open tag")?
-            }
-        }
-    };
-    let artist = tag.artist().ok_or("Fail to open tag title")?;
-    let title = string_cleaner::clean_title_before_api_call(
-        &tag.title().ok_or("Fail to open tag title")?,
-        &artist,
-    );
+impl LyricsProvider for Lrclib {
+    fn name(&self) -> &'static str {
+        "lrclib"
+    }
 
-    let artist: String = form_urlencoded::byte_serialize(artist.as_bytes()).collect();
-    let title: String = form_urlencoded::byte_serialize(title.as_bytes()).collect();
-    let query = format!(
-        "https://lrclib.net/api/get?artist_name={}&track_name={}",
-        artist, title
-    );
-    let ly = fetch(&query)?;
-    log::info!("Query for lrclib: {}", query);
-    if !ly.is_empty() {
-        log::info!("Lyrics Found From lrclib");
-        let lyric_final = translate(lang, &ly)?;
+    fn search(&self, query: &Query) -> Result<Vec<Candidate>, Box<dyn Error>> {
+        let title = string_cleaner::clean_title_before_api_call(&query.title, &query.artist);
 
-        if keep_lrc {
-            fs::write(change_ext(&musicfile, "lrc"), &lyric_final)?;
-            log::info!("Written lrc file from lrclib");
-        }
+        let artist: String = form_urlencoded::byte_serialize(query.artist.as_bytes()).collect();
+        let title: String = form_urlencoded::byte_serialize(title.as_bytes()).collect();
+        // Search gives every version of the song, `get` only the one lrclib thinks is best
+        let url = format!(
+            "https://lrclib.net/api/search?artist_name={}&track_name={}",
+            artist, title
+        );
+        log::info!("Query for lrclib: {}", url);
+        let results = fetch(&url)?;
+        log::info!("{} results from lrclib", results.len());
 
-        if !lyric_final.is_empty() {
-            if tag.tag_type() == TagType::Id3v2 {
-                tag.insert_text(ItemKey::UnsyncLyrics, lyric_final);
-            } else {
-                tag.insert_text(ItemKey::Lyrics, lyric_final);
-            }
-            tag.save_to_path(musicfile, WriteOptions::default())?;
-        }
+        let candidates = results
+            .into_iter()
+            .filter_map(|lyr| {
+                let (lyrics, synced) = match (lyr.synced_lyrics, lyr.plain_lyrics) {
+                    (Some(synced), _) if !synced.is_empty() => lrc::normalize(&synced),
+                    (_, Some(plain)) if !plain.is_empty() => (plain, false),
+                    _ => return None,
+                };
+                Some(Candidate {
+                    source: self.name(),
+                    lyrics,
+                    synced,
+                    lang: None,
+                    duration: lyr.duration,
+                    score: text_score(query, &lyr.track_name, &lyr.artist_name),
+                })
+            })
+            .collect();
+        Ok(candidates)
     }
-    Ok(())
 }
 
 #[derive(Debug, Deserialize)]
 struct ApiResponse {
+    #[serde(rename = "trackName", default)]
+    track_name: String,
+    #[serde(rename = "artistName", default)]
+    artist_name: String,
+    duration: Option<f64>,
     #[serde(rename = "plainLyrics")]
-    plain_lyrics: String,
+    plain_lyrics: Option<String>,
     #[serde(rename = "syncedLyrics")]
-    synced_lyrics: String,
+    synced_lyrics: Option<String>,
 }
 
-fn fetch(query: &str) -> Result<String, Box<dyn Error>> {
+fn fetch(query: &str) -> Result<Vec<ApiResponse>, Box<dyn Error>> {
     let lyr = ureq::get(query)
         .header(
             "User-Agent",
@@ -83,12 +70,6 @@ fn fetch(query: &str) -> Result<String, Box<dyn Error>> {
         )
         .call()?
         .body_mut()
-        .read_json::<ApiResponse>()?;
-    if !lyr.synced_lyrics.is_empty() {
-        Ok(lyr.synced_lyrics)
-    } else if !lyr.plain_lyrics.is_empty() {
-        Ok(lyr.plain_lyrics)
-    } else {
-        Ok(String::new())
-    }
+        .read_json::<Vec<ApiResponse>>()?;
+    Ok(lyr)
 }
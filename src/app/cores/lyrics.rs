@@ -1,184 +1,543 @@
-use crate::USERAGENT;
-use crate::app::cores::files::{change_ext, file_finder};
-use crate::app::cores::translate::translate;
+use crate::app::cores::files::{change_ext, file_finder, writable_tag};
+use crate::app::cores::language;
+use crate::app::cores::lrc::{self, Lrc, LrcLine, Word};
+use crate::app::cores::lyrics_provider::{Candidate, LyricsProvider, Query};
+use crate::app::cores::subtitle::{self, Cue, SubtitleFormat};
 use crate::app::cores::ytdlp::Entry;
 
+use lofty::TextEncoding;
+use lofty::config::{ParseOptions, WriteOptions};
+use lofty::file::FileType;
+use lofty::flac::FlacFile;
+use lofty::id3::v2::{
+    BinaryFrame, Frame, FrameId, Id3v2Tag, SyncTextContentType, SynchronizedTextFrame,
+    TextInformationFrame, TimestampFormat, UnsynchronizedTextFrame,
+};
+use lofty::iff::wav::WavFile;
+use lofty::mp4::{Atom, AtomData, AtomIdent, Ilst, Mp4File};
+use lofty::mpeg::MpegFile;
+use lofty::ogg::{OpusFile, VorbisComments, VorbisFile};
+use lofty::prelude::*;
+use lofty::probe::Probe;
 use lofty::tag::TagType;
 use log::{error, info};
+use regex::Regex;
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::error::Error;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+/// Auto generated cues this short are the hand over between two rolling lines, in milliseconds
+const ROLLING_GAP: u64 = 50;
+
+/// Subtitles of the downloaded video itself, or the lrc yt-dlp converted from them
+pub struct Youtube {
+    pub filename: String,
+    pub directory: String,
+    pub sanitize: bool,
+    pub lang_code: String,
+    pub subtitles: Option<HashMap<String, Vec<Entry>>>,
+}
+
+impl Youtube {
+    /// The subtitle in the wanted language first, then any other one
+    fn subtitle_lyrics(&self) -> Result<Option<(String, String)>, Box<dyn Error>> {
+        let Some(entris) = &self.subtitles else {
+            return Ok(None);
+        };
+        let wanted = entris.get_key_value(self.lang_code.as_str());
+        for (lang, subs) in wanted.into_iter().chain(entris.iter()) {
+            if let Some(sub) = subs.iter().find(|sub| sub.ext.trim() == "vtt") {
+                return Ok(Some((vtt_to_lrc(sub.fetch()?), lang.clone())));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl LyricsProvider for Youtube {
+    fn name(&self) -> &'static str {
+        "youtube"
+    }
+
+    fn search(&self, _query: &Query) -> Result<Vec<Candidate>, Box<dyn Error>> {
+        let (lyrics, lang) = match self.subtitle_lyrics()? {
+            Some(found) => found,
+            None => {
+                let lyrics_file = match file_finder(&self.directory, &self.filename, &["lrc"]) {
+                    Some(path) => path,
+                    None => {
+                        error!("Lyrics file not found.");
+                        return Err("Lyrics file not found.".into());
+                    }
+                };
+                let lyrics = if self.sanitize {
+                    lyrics_cleaner(&fs::read_to_string(&lyrics_file)?)?
+                } else {
+                    fs::read_to_string(&lyrics_file)?
+                };
+                // yt-dlp only writes the subtitle of the asked language
+                (lyrics, self.lang_code.clone())
+            }
+        };
+        let (lyrics, synced) = lrc::normalize(&lyrics);
+        Ok(vec![Candidate {
+            source: self.name(),
+            synced,
+            lyrics,
+            lang: Some(lang),
+            duration: None,
+            // Subtitles of the video itself always belong to the song
+            score: 1.0,
+        }])
+    }
+}
+
+/// Where the translation goes in sidecar mode, `song.mp3` gets `song.<lang>.lrc`
+pub fn sidecar_path(music_file: &Path, lang_code: &str) -> PathBuf {
+    music_file.with_extension(format!("{lang_code}.lrc"))
+}
+
+/// Whether any tag of `music_file` already holds lyrics
+pub fn has_lyrics(music_file: &Path) -> Result<bool, Box<dyn Error>> {
+    let tagged_file = Probe::open(music_file)?.read()?;
+    Ok(tagged_file.tags().iter().any(|tag| {
+        [ItemKey::Lyrics, ItemKey::UnsyncLyrics]
+            .into_iter()
+            .any(|key| {
+                tag.get_string(key)
+                    .is_some_and(|lyrics| !lyrics.trim().is_empty())
+            })
+    }))
+}
 
-static VALID_FORMAT: &[&str] = &["flac", "opus", "mp3", "m4a"];
+/// The lyrics in the tag of `music_file`, the timed version when the tag has one
+pub fn read(music_file: &Path) -> Result<Option<String>, Box<dyn Error>> {
+    let mut file = fs::File::open(music_file)?;
+    let file_type = Probe::open(music_file)?.guess_file_type()?.file_type();
+    let synced = match file_type {
+        Some(FileType::Mpeg) => MpegFile::read_from(&mut file, ParseOptions::new())?
+            .id3v2()
+            .and_then(sylt_to_lrc),
+        Some(FileType::Wav) => WavFile::read_from(&mut file, ParseOptions::new())?
+            .id3v2()
+            .and_then(sylt_to_lrc),
+        Some(FileType::Flac) => FlacFile::read_from(&mut file, ParseOptions::new())?
+            .vorbis_comments()
+            .and_then(|tag| tag.get("SYNCEDLYRICS"))
+            .map(|lyrics| lyrics.to_string()),
+        Some(FileType::Opus) => OpusFile::read_from(&mut file, ParseOptions::new())?
+            .vorbis_comments()
+            .get("SYNCEDLYRICS")
+            .map(|lyrics| lyrics.to_string()),
+        Some(FileType::Vorbis) => VorbisFile::read_from(&mut file, ParseOptions::new())?
+            .vorbis_comments()
+            .get("SYNCEDLYRICS")
+            .map(|lyrics| lyrics.to_string()),
+        Some(FileType::Mp4) => Mp4File::read_from(&mut file, ParseOptions::new())?
+            .ilst()
+            .and_then(|tag| tag.get(&freeform("SYNCEDLYRICS")))
+            .and_then(|atom| {
+                atom.data().find_map(|data| match data {
+                    AtomData::UTF8(lyrics) => Some(lyrics.clone()),
+                    _ => None,
+                })
+            }),
+        _ => None,
+    };
+    if synced.is_some() {
+        return Ok(synced);
+    }
+    let tagged_file = Probe::open(music_file)?.read()?;
+    Ok(tagged_file.tags().iter().find_map(|tag| {
+        [ItemKey::Lyrics, ItemKey::UnsyncLyrics]
+            .into_iter()
+            .find_map(|key| tag.get_string(key))
+            .map(|lyrics| lyrics.to_string())
+    }))
+}
 
-pub fn work(
-    filename: &str,
+/// Turn the `SYLT` frame written by `embed` back into LRC
+fn sylt_to_lrc(tag: &Id3v2Tag) -> Option<String> {
+    let Some(Frame::Binary(frame)) = tag.get(&FrameId::Valid(Cow::Borrowed("SYLT"))) else {
+        return None;
+    };
+    let sylt = SynchronizedTextFrame::parse(&frame.data, frame.flags()).ok()?;
+    if !matches!(sylt.timestamp_format, TimestampFormat::MS) {
+        return None;
+    }
+    // One entry per word, a line feed starts every line. Older files left it off the first
+    let word_synced = sylt.content.iter().any(|(_, text)| text.starts_with('\n'));
+    let mut lrc = Lrc::default();
+    for (time, text) in sylt.content {
+        let time = time as u64;
+        if !word_synced {
+            lrc.lines.push(LrcLine::new(time, &text));
+            continue;
+        }
+        let word = Word {
+            time,
+            text: text.trim_start_matches('\n').to_string(),
+        };
+        match lrc.lines.last_mut() {
+            Some(line) if !text.starts_with('\n') => {
+                line.text.push_str(&word.text);
+                line.words.push(word);
+            }
+            _ => lrc.lines.push(LrcLine {
+                time,
+                text: word.text.clone(),
+                words: vec![word],
+            }),
+        }
+    }
+    for line in lrc.lines.iter_mut() {
+        line.text = line.text.trim().to_string();
+    }
+    lrc.is_synced().then(|| lrc.to_string())
+}
+
+/// Write the lyrics and their language into the tag of `music_file`,
+/// and next to it as `.lrc` when asked or when the tag can not take them
+pub fn embed(
     music_file: &Path,
-    format_name: &str,
-    directory: &str,
-    sanitize: bool,
-    lang_code: &str,
-    potential_lyrics: Option<HashMap<String, Vec<Entry>>>,
+    lyrics: &str,
+    lang: Option<&str>,
     keep_lrc: bool,
 ) -> Result<(), Box<dyn Error>> {
-    let mut lyrics = String::new();
-    if let Some(entris) = potential_lyrics {
-        for e in &entris {
-            if e.0 == lang_code {
-                for sub in e.1 {
-                    if sub.ext.trim() == "vtt" {
-                        let mut response =
-                            ureq::get(&sub.url).header("User-Agent", USERAGENT).call()?;
-                        let vtt_content = response.body_mut().read_to_string()?;
-                        lyrics = vtt_to_lrc(vtt_content);
-                        break;
-                    }
-                }
+    let lrc_file = change_ext(music_file, "lrc");
+    if keep_lrc {
+        fs::write(&lrc_file, lyrics)?;
+        info!("Written lrc file");
+    }
+    match write_tag(music_file, lyrics, lang) {
+        Ok(_) => info!("Lyrics successfully saved to the music file."),
+        Err(e) => {
+            error!("Fail to embed lyrics into the tag: {e}");
+            if !keep_lrc {
+                fs::write(&lrc_file, lyrics)?;
+                info!("Written lrc file instead");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Remove the lyrics `write_tag` writes, the timed ones too so `read` finds nothing
+pub fn clear(music_file: &Path) -> Result<(), Box<dyn Error>> {
+    let mut file = fs::File::open(music_file)?;
+    let file_type = Probe::open(music_file)?.guess_file_type()?.file_type();
+    match file_type {
+        Some(FileType::Mpeg) => {
+            let mpeg = MpegFile::read_from(&mut file, ParseOptions::new())?;
+            if let Some(mut tag) = mpeg.id3v2().cloned() {
+                id3v2_clear(&mut tag);
+                tag.save_to_path(music_file, WriteOptions::default())?;
             }
         }
-        if lyrics.is_empty() && !&entris.is_empty() {
-            let sub = entris.values().collect::<Vec<_>>()[0];
-            for sub_format in sub {
-                if sub_format.ext.trim() == "vtt" {
-                    let mut response = ureq::get(&sub_format.url)
-                        .header("User-Agent", USERAGENT)
-                        .call()?;
-                    let vtt_content = response.body_mut().read_to_string()?;
-                    lyrics = translate(lang_code, &vtt_to_lrc(vtt_content))?;
-                    break;
-                }
+        Some(FileType::Wav) => {
+            let wav = WavFile::read_from(&mut file, ParseOptions::new())?;
+            if let Some(mut tag) = wav.id3v2().cloned() {
+                id3v2_clear(&mut tag);
+                tag.save_to_path(music_file, WriteOptions::default())?;
             }
         }
-    }
-    if lyrics.is_empty() {
-        let lyrics_file = match file_finder(directory, filename, &["lrc"]) {
-            Some(path) => path,
-            None => {
-                error!("Lyrics file not found.");
-                return Err("Lyrics file not found.".into());
+        Some(FileType::Flac) => {
+            let flac = FlacFile::read_from(&mut file, ParseOptions::new())?;
+            if let Some(mut tag) = flac.vorbis_comments().cloned() {
+                vorbis_clear(&mut tag);
+                tag.save_to_path(music_file, WriteOptions::default())?;
             }
-        };
-        lyrics = if sanitize {
-            lyrics_cleaner(&fs::read_to_string(&lyrics_file)?)?
-        } else {
-            fs::read_to_string(&lyrics_file)?
-        };
-        fs::remove_file(&lyrics_file)?;
+        }
+        Some(FileType::Opus) => {
+            let opus = OpusFile::read_from(&mut file, ParseOptions::new())?;
+            let mut tag = opus.vorbis_comments().clone();
+            vorbis_clear(&mut tag);
+            tag.save_to_path(music_file, WriteOptions::default())?;
+        }
+        Some(FileType::Vorbis) => {
+            let vorbis = VorbisFile::read_from(&mut file, ParseOptions::new())?;
+            let mut tag = vorbis.vorbis_comments().clone();
+            vorbis_clear(&mut tag);
+            tag.save_to_path(music_file, WriteOptions::default())?;
+        }
+        Some(FileType::Mp4) => {
+            let mp4 = Mp4File::read_from(&mut file, ParseOptions::new())?;
+            if let Some(mut tag) = mp4.ilst().cloned() {
+                let _ = tag.remove(&AtomIdent::Fourcc(*b"\xa9lyr"));
+                let _ = tag.remove(&freeform("SYNCEDLYRICS"));
+                tag.save_to_path(music_file, WriteOptions::default())?;
+            }
+        }
+        _ => {
+            let mut tagged_file = Probe::open(music_file)?.read()?;
+            let Some(tag) = tagged_file.primary_tag_mut() else {
+                return Ok(());
+            };
+            tag.remove_key(ItemKey::Lyrics);
+            tag.remove_key(ItemKey::UnsyncLyrics);
+            tag.save_to_path(music_file, WriteOptions::default())?;
+        }
     }
-    if !lyrics.is_empty() && VALID_FORMAT.contains(&format_name) {
-        use lofty::config::WriteOptions;
-        use lofty::prelude::*;
-        use lofty::probe::Probe;
-        use lofty::tag::Tag;
+    Ok(())
+}
 
-        let mut tagged_file = Probe::open(music_file)?.read()?;
+fn id3v2_clear(tag: &mut Id3v2Tag) {
+    let _ = tag.remove(&FrameId::Valid(Cow::Borrowed("USLT")));
+    let _ = tag.remove(&FrameId::Valid(Cow::Borrowed("SYLT")));
+}
 
-        let tag = match tagged_file.primary_tag_mut() {
-            Some(primary_tag) => primary_tag,
-            None => {
-                if let Some(first_tag) = tagged_file.first_tag_mut() {
-                    first_tag
-                } else {
-                    let tag_type = tagged_file.primary_tag_type();
+fn vorbis_clear(tag: &mut VorbisComments) {
+    for key in ["LYRICS", "UNSYNCEDLYRICS", "SYNCEDLYRICS"] {
+        let _ = tag.remove(key);
+    }
+}
 
-                    tagged_file.insert_tag(Tag::new(tag_type));
+/// Every format gets clean text for players without sync support, and the timed version
+/// where the format has a place for it: `SYLT` for ID3v2, `SYNCEDLYRICS` for Vorbis and MP4
+fn write_tag(music_file: &Path, lyrics: &str, lang: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let lrc = Lrc::parse(lyrics);
+    let plain = if lrc.is_synced() {
+        lrc.plain()
+    } else {
+        lyrics.trim().to_string()
+    };
+    let synced = lrc.is_synced().then(|| lrc.to_string());
+    let lang = lang.and_then(language::iso639_2);
 
-                    tagged_file.primary_tag_mut().ok_or("Fail to open tag")?
-                }
+    let mut file = fs::File::open(music_file)?;
+    let file_type = Probe::open(music_file)?.guess_file_type()?.file_type();
+    match file_type {
+        Some(FileType::Mpeg) => {
+            let mpeg = MpegFile::read_from(&mut file, ParseOptions::new())?;
+            let mut tag = mpeg.id3v2().cloned().unwrap_or_default();
+            id3v2_lyrics(&mut tag, &lrc, &plain, lang)?;
+            tag.save_to_path(music_file, WriteOptions::default())?;
+        }
+        Some(FileType::Wav) => {
+            // RIFF INFO has no lyrics field, they go in the ID3v2 chunk
+            let wav = WavFile::read_from(&mut file, ParseOptions::new())?;
+            let mut tag = wav.id3v2().cloned().unwrap_or_default();
+            id3v2_lyrics(&mut tag, &lrc, &plain, lang)?;
+            tag.save_to_path(music_file, WriteOptions::default())?;
+        }
+        Some(FileType::Flac) => {
+            let flac = FlacFile::read_from(&mut file, ParseOptions::new())?;
+            let mut tag = flac.vorbis_comments().cloned().unwrap_or_default();
+            vorbis_lyrics(&mut tag, &plain, synced, lang);
+            tag.save_to_path(music_file, WriteOptions::default())?;
+        }
+        Some(FileType::Opus) => {
+            let opus = OpusFile::read_from(&mut file, ParseOptions::new())?;
+            let mut tag = opus.vorbis_comments().clone();
+            vorbis_lyrics(&mut tag, &plain, synced, lang);
+            tag.save_to_path(music_file, WriteOptions::default())?;
+        }
+        Some(FileType::Vorbis) => {
+            let vorbis = VorbisFile::read_from(&mut file, ParseOptions::new())?;
+            let mut tag = vorbis.vorbis_comments().clone();
+            vorbis_lyrics(&mut tag, &plain, synced, lang);
+            tag.save_to_path(music_file, WriteOptions::default())?;
+        }
+        Some(FileType::Mp4) => {
+            let mp4 = Mp4File::read_from(&mut file, ParseOptions::new())?;
+            let mut tag = mp4.ilst().cloned().unwrap_or_default();
+            ilst_lyrics(&mut tag, &plain, synced, lang);
+            tag.save_to_path(music_file, WriteOptions::default())?;
+        }
+        _ => {
+            let mut tagged_file = Probe::open(music_file)?.read()?;
+            let tag = writable_tag(&mut tagged_file).ok_or("Fail to open tag")?;
+            // ID3v2 in AIFF and AAC only has USLT
+            let key = if tag.tag_type() == TagType::Id3v2 {
+                ItemKey::UnsyncLyrics
+            } else {
+                ItemKey::Lyrics
+            };
+            tag.insert_text(key, lyrics.to_string());
+            if let Some(lang) = lang {
+                tag.insert_text(ItemKey::Language, lang.to_string());
             }
-        };
-        if keep_lrc {
-            fs::write(change_ext(&music_file, "lrc"), &lyrics)?;
-            info!("Written lrc file");
+            tag.save_to_path(music_file, WriteOptions::default())?;
         }
+    }
+    Ok(())
+}
 
-        if tag.tag_type() == TagType::Id3v2 {
-            tag.insert_text(ItemKey::UnsyncLyrics, lyrics);
-        } else {
-            tag.insert_text(ItemKey::Lyrics, lyrics);
+fn id3v2_lyrics(
+    tag: &mut Id3v2Tag,
+    lrc: &Lrc,
+    plain: &str,
+    lang: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    let lang_code: [u8; 3] = lang
+        .and_then(|lang| lang.as_bytes().try_into().ok())
+        .unwrap_or(*b"XXX");
+    let _ = tag.remove(&FrameId::Valid(Cow::Borrowed("USLT")));
+    let _ = tag.remove(&FrameId::Valid(Cow::Borrowed("SYLT")));
+    tag.insert(Frame::UnsynchronizedText(UnsynchronizedTextFrame::new(
+        TextEncoding::UTF8,
+        lang_code,
+        "",
+        plain.to_string(),
+    )));
+    if lrc.is_synced() {
+        // Karaoke players expect one entry per word, then every line starts with a line feed
+        let line_start = if lrc.has_words() { "\n" } else { "" };
+        let mut content: Vec<(u32, String)> = vec![];
+        for line in &lrc.lines {
+            let words: Vec<&Word> = line.words.iter().filter(|w| !w.text.is_empty()).collect();
+            if words.is_empty() {
+                content.push((line.time as u32, format!("{line_start}{}", line.text)));
+                continue;
+            }
+            for (index, word) in words.into_iter().enumerate() {
+                let text = if index == 0 {
+                    format!("{line_start}{}", word.text)
+                } else {
+                    word.text.clone()
+                };
+                content.push((word.time as u32, text));
+            }
         }
-        tag.save_to_path(music_file, WriteOptions::default())?;
-
-        info!("Lyrics successfully saved to the music file.");
-        info!("Lyrics file removed after processing.");
+        let sylt = SynchronizedTextFrame::new(
+            TextEncoding::UTF8,
+            lang_code,
+            TimestampFormat::MS,
+            SyncTextContentType::Lyrics,
+            None,
+            content,
+        );
+        tag.insert(Frame::Binary(BinaryFrame::new(
+            FrameId::Valid(Cow::Borrowed("SYLT")),
+            sylt.as_bytes(WriteOptions::default())?,
+        )));
+    }
+    if let Some(lang) = lang {
+        tag.insert(Frame::Text(TextInformationFrame::new(
+            FrameId::Valid(Cow::Borrowed("TLAN")),
+            TextEncoding::UTF8,
+            lang.to_string(),
+        )));
     }
     Ok(())
 }
 
-use std::fmt;
+fn vorbis_lyrics(
+    tag: &mut VorbisComments,
+    plain: &str,
+    synced: Option<String>,
+    lang: Option<&str>,
+) {
+    tag.insert("LYRICS".to_string(), plain.to_string());
+    match synced {
+        Some(synced) => tag.insert("SYNCEDLYRICS".to_string(), synced),
+        None => {
+            let _ = tag.remove("SYNCEDLYRICS");
+        }
+    }
+    if let Some(lang) = lang {
+        tag.insert("LANGUAGE".to_string(), lang.to_string());
+    }
+}
+
+fn freeform(name: &'static str) -> AtomIdent<'static> {
+    AtomIdent::Freeform {
+        mean: Cow::Borrowed("com.apple.iTunes"),
+        name: Cow::Borrowed(name),
+    }
+}
 
-use regex::Regex;
+fn ilst_lyrics(tag: &mut Ilst, plain: &str, synced: Option<String>, lang: Option<&str>) {
+    tag.replace_atom(Atom::new(
+        AtomIdent::Fourcc(*b"\xa9lyr"),
+        AtomData::UTF8(plain.to_string()),
+    ));
+    match synced {
+        Some(synced) => {
+            tag.replace_atom(Atom::new(freeform("SYNCEDLYRICS"), AtomData::UTF8(synced)))
+        }
+        None => {
+            let _ = tag.remove(&freeform("SYNCEDLYRICS"));
+        }
+    }
+    if let Some(lang) = lang {
+        tag.replace_atom(Atom::new(
+            freeform("LANGUAGE"),
+            AtomData::UTF8(lang.to_string()),
+        ));
+    }
+}
 
+/// Drop the repeated and rolling lines of youtube auto generated lyrics
 fn lyrics_cleaner(lyrics: &str) -> Result<String, Box<dyn Error>> {
-    let lines: Vec<&str> = lyrics.lines().filter(|line| !line.is_empty()).collect();
-    let time_stamps = collect_time_stamp(lyrics)?;
+    let mut lrc = Lrc::parse(lyrics);
+    if !lrc.is_synced() {
+        return Err("lyrics_cleaner: no timed line".into());
+    }
+    let lines = lrc.lines.drain(..).map(|line| (line.time, line)).collect();
+    lrc.lines = dedupe(lines)
+        .into_iter()
+        .map(|(_, mut line)| {
+            line.words.clear();
+            line
+        })
+        .collect();
+    Ok(lrc.to_string())
+}
 
-    let lrclines = turn_into_lrcline(time_stamps, lines)?;
-    let mut cleaned_lrc: Vec<&LrcLine> = vec![];
+/// `lyrics_cleaner` for the auto generated subtitles of a video, cues keep their end
+pub fn caption_cleaner(cues: Vec<Cue>) -> Vec<Cue> {
+    let lines = cues
+        .into_iter()
+        // The few milliseconds cues between two rolling lines only repeat the last one
+        .filter(|cue| cue.end.saturating_sub(cue.start) > ROLLING_GAP)
+        .map(|cue| {
+            // A rolling cue shows the line before it again on top of the new one
+            let text = cue.text.lines().last().unwrap_or_default();
+            (cue.end, LrcLine::new(cue.start, text))
+        })
+        .collect();
+    dedupe(lines)
+        .into_iter()
+        .map(|(end, line)| Cue {
+            start: line.time,
+            end,
+            text: line.text,
+        })
+        .collect()
+}
 
-    let mut counter = 0;
-    while counter < lrclines.len() {
-        if counter == 0 {
-            cleaned_lrc.push(&lrclines[counter]);
+/// Merge the lines is_similar finds repeated, each line carries when it ends
+fn dedupe(lines: Vec<(u64, LrcLine)>) -> Vec<(u64, LrcLine)> {
+    let mut cleaned: Vec<(u64, LrcLine)> = vec![];
+    for (end, line) in lines {
+        let Some((last_end, last)) = cleaned.last_mut() else {
+            cleaned.push((end, line));
+            continue;
         };
-        let last_cleaned = cleaned_lrc
-            .last()
-            .ok_or("cleaned_lrc: fail take the last cleaned_lrc")?;
-        match last_cleaned.is_similar(&lrclines[counter])? {
+        match is_similar(last, &line) {
             Similarity::Time => {
-                let merged = Box::new(last_cleaned.merge(&lrclines[counter]));
-                cleaned_lrc.pop();
-                cleaned_lrc.push(Box::leak(merged));
+                last.text = format!("{} {}", last.text, line.text);
             }
             Similarity::AinB => {
-                let merged = Box::new(last_cleaned.merge(&lrclines[counter]));
-                cleaned_lrc.pop();
-                cleaned_lrc.push(Box::leak(merged));
+                last.text = line.text;
             }
             Similarity::None => {
-                cleaned_lrc.push(&lrclines[counter]);
+                cleaned.push((end, line));
+                continue;
             }
-            _ => {}
+            Similarity::Content | Similarity::BinA => {}
         }
-        counter += 1;
+        *last_end = end.max(*last_end);
     }
-    let mut cleaned_lrc_strings: Vec<String> = vec![];
-    for lrc in cleaned_lrc {
-        cleaned_lrc_strings.push(lrc.clone().clean().return_lrc());
+    for (_, line) in cleaned.iter_mut() {
+        line.text = clean(&line.text);
     }
-    Ok(cleaned_lrc_strings.join("\n"))
+    cleaned
 }
 
-fn turn_into_lrcline(times: Vec<String>, lines: Vec<&str>) -> Result<Vec<LrcLine>, Box<dyn Error>> {
-    let mut counter = 0;
-    let mut without: Vec<LrcLine> = vec![];
-    while counter < times.len() {
-        let time = times[counter].trim_matches(|c| c == '[' || c == ']');
-        let mut time_parts = time.split(':');
-        let min = time_parts
-            .next()
-            .ok_or("turn_into_lrcline: fail to parse min")?
-            .parse::<u8>()?;
-        let sec = time_parts
-            .next()
-            .ok_or("turn_into_lrcline: fail to parse sec")?
-            .parse::<f32>()?;
-        let lrc = LrcLine {
-            timestamp: TimeStamp {
-                minutes: min,
-                seconds: sec,
-            },
-            content: lines[counter + 2]
-                .replace(&times[counter], "")
-                .trim()
-                .to_string(),
-        };
-        without.push(lrc);
-        counter += 1
-    }
-    Ok(without)
-}
 enum Similarity {
     Content,
     AinB,
@@ -187,121 +546,184 @@ enum Similarity {
     None,
 }
 
-#[derive(Debug, Clone)]
-struct LrcLine {
-    timestamp: TimeStamp,
-    content: String,
-}
-impl LrcLine {
-    fn return_lrc(&self) -> String {
-        format!(
-            "[{:02}:{:05.2}]{}",
-            self.timestamp.minutes, self.timestamp.seconds, self.content
-        )
-    }
-    fn clean(&mut self) -> LrcLine {
-        let regex_patterns = [r"\\[A-Za-z]", r"</[A-Za-z]>", r"<[A-Za-z]>"];
-        let mut cleaned_content = self.content.clone();
-        for pattern in regex_patterns.iter() {
-            let regex = regex::Regex::new(pattern).unwrap();
-            cleaned_content = regex.replace_all(&cleaned_content, "").to_string();
-        }
-        self.content = cleaned_content;
-        self.clone()
-    }
-    fn is_similar(&self, compared: &LrcLine) -> Result<Similarity, Box<dyn Error>> {
-        let compared_in_self = self.content.contains(compared.content.trim());
-        let self_in_compared = compared.content.contains(self.content.trim());
-        let minustime = self.timestamp.minus(&compared.timestamp)?;
-
-        let strict_similar_content =
-            textdistance::nstr::cosine(&self.content, &compared.content) > 0.9;
-        let content_check = textdistance::nstr::cosine(&self.content, &compared.content) > 0.85;
-
-        let time_status =
-            minustime.minutes == 0 && -0.5 <= minustime.seconds && minustime.seconds <= 0.0;
-        let time_status_less_strict =
-            minustime.minutes == 0 && -2.0 < minustime.seconds && minustime.seconds <= 0.0;
-
-        if (content_check && time_status) || (strict_similar_content && time_status_less_strict) {
-            Ok(Similarity::Content)
-        } else if compared_in_self && time_status {
-            Ok(Similarity::BinA)
-        } else if self_in_compared && time_status {
-            Ok(Similarity::AinB)
-        } else if time_status {
-            Ok(Similarity::Time)
-        } else {
-            Ok(Similarity::None)
-        }
-    }
-    fn merge(&self, merge_item: &LrcLine) -> LrcLine {
-        LrcLine {
-            timestamp: self.timestamp.clone(),
-            content: format!("{} {}", self.content, merge_item.content),
-        }
+fn clean(content: &str) -> String {
+    let regex_patterns = [r"\\[A-Za-z]", r"</[A-Za-z]>", r"<[A-Za-z]>"];
+    let mut cleaned_content = content.to_string();
+    for pattern in regex_patterns.iter() {
+        let regex = Regex::new(pattern).unwrap();
+        cleaned_content = regex.replace_all(&cleaned_content, "").to_string();
     }
+    cleaned_content.trim().to_string()
 }
 
-impl fmt::Display for LrcLine {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "TimeStamp: {} Content: {}", self.timestamp, self.content)
+fn is_similar(last: &LrcLine, compared: &LrcLine) -> Similarity {
+    let compared_in_self = last.text.contains(compared.text.trim());
+    let self_in_compared = compared.text.contains(last.text.trim());
+    // Lines are sorted so `compared` never starts before `last`
+    let gap = compared.time.saturating_sub(last.time);
+
+    let strict_similar_content = textdistance::nstr::cosine(&last.text, &compared.text) > 0.9;
+    let content_check = textdistance::nstr::cosine(&last.text, &compared.text) > 0.85;
+
+    let time_status = gap <= 500;
+    let time_status_less_strict = gap < 2000;
+
+    if (content_check && time_status) || (strict_similar_content && time_status_less_strict) {
+        Similarity::Content
+    } else if compared_in_self && time_status {
+        Similarity::BinA
+    } else if self_in_compared && time_status {
+        Similarity::AinB
+    } else if time_status {
+        Similarity::Time
+    } else {
+        Similarity::None
     }
 }
-#[derive(Debug, Clone)]
-struct TimeStamp {
-    minutes: u8,
-    seconds: f32,
-}
-impl TimeStamp {
-    fn minus(&self, b: &TimeStamp) -> Result<TimeStamp, Box<dyn Error>> {
-        let (minutes, seconds) = (self.minutes - b.minutes, self.seconds - b.seconds);
-        Ok(TimeStamp { minutes, seconds })
+
+fn vtt_to_lrc(vtt: String) -> String {
+    let mut lrc = Lrc::default();
+    for cue in subtitle::parse(&vtt, SubtitleFormat::Vtt) {
+        lrc.lines
+            .push(LrcLine::new(cue.start, &cue.text.replace('\n', " ")));
     }
+    lrc.to_string()
 }
-impl fmt::Display for TimeStamp {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "[{:02}:{:05.2}]", self.minutes, self.seconds)
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sylt_round_trip(lyrics: &str) -> Lrc {
+        let lrc = Lrc::parse(lyrics);
+        let mut tag = Id3v2Tag::default();
+        id3v2_lyrics(&mut tag, &lrc, &lrc.plain(), Some("eng")).unwrap();
+        Lrc::parse(&sylt_to_lrc(&tag).unwrap())
     }
-}
 
-fn collect_time_stamp(lyrics: &str) -> Result<Vec<String>, Box<dyn Error>> {
-    let time_stamp_regex = Regex::new(r"\[[0-9]+:[0-9]+\.[0-9]+\]").unwrap();
-    Ok(time_stamp_regex
-        .find_iter(lyrics)
-        .map(|time| time.as_str().to_string())
-        .collect())
-}
+    #[test]
+    fn sylt_keeps_lines() {
+        let lrc = sylt_round_trip("[00:01.00]First line\n[00:02.50]Second line");
+        assert_eq!(
+            lrc,
+            Lrc::parse("[00:01.00]First line\n[00:02.50]Second line")
+        );
+    }
 
-//May need to clean this code later
-fn vtt_to_lrc(str: String) -> String {
-    let mut lrc: Vec<LrcLine> = Vec::new();
-    let mut time: Option<&str> = None;
-    let mut current_time_is_unused = false;
-    for (_, st) in str.lines().enumerate() {
-        if st.contains("-->") {
-            time = st.split("-->").nth(0);
-            current_time_is_unused = true;
-        } else if let Some(time) = time {
-            if current_time_is_unused {
-                let time: Vec<&str> = time.split(":").collect();
-                lrc.push(LrcLine {
-                    timestamp: TimeStamp {
-                        minutes: time[1].trim().parse().unwrap(),
-                        seconds: time[2].trim().parse().unwrap(),
-                    },
-                    content: st.to_string(),
-                });
-                current_time_is_unused = false;
-            } else if !st.is_empty() {
-                let index = lrc.len() - 1;
-                lrc[index].content = format!("{} {st}", lrc[lrc.len() - 1].content);
-            }
+    #[test]
+    fn sylt_keeps_words_and_plain_lines() {
+        let lrc = sylt_round_trip(
+            "[00:01.00]<00:01.00>Hello <00:01.50>world\n\
+             [00:02.00]No words here\n\
+             [00:03.00]<00:03.00>Bye <00:03.40>now",
+        );
+        let lines: Vec<(u64, &str)> = lrc
+            .lines
+            .iter()
+            .map(|line| (line.time, line.text.as_str()))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                (1000, "Hello world"),
+                (2000, "No words here"),
+                (3000, "Bye now")
+            ]
+        );
+        let times: Vec<u64> = lrc.lines[2].words.iter().map(|word| word.time).collect();
+        assert_eq!(times, [3000, 3400]);
+    }
+
+    fn cue(start: u64, end: u64, text: &str) -> Cue {
+        Cue {
+            start,
+            end,
+            text: text.to_string(),
         }
     }
-    return lrc
-        .iter()
-        .map(|x| x.return_lrc())
-        .collect::<Vec<String>>()
-        .join("\n");
+
+    #[test]
+    fn caption_cleaner_drops_rolling_repeats() {
+        let cues = vec![
+            cue(0, 2000, "hello"),
+            // Shows the last line again between two rolling cues
+            cue(2000, 2010, "hello"),
+            cue(2010, 4000, "hello\nworld out there"),
+            cue(3500, 5000, "world out there"),
+        ];
+        assert_eq!(
+            caption_cleaner(cues),
+            [cue(0, 2000, "hello"), cue(2010, 5000, "world out there")]
+        );
+    }
+
+    #[test]
+    fn dedupe_keeps_the_longer_line() {
+        let lines = vec![
+            (1000, LrcLine::new(0, "see you")),
+            (2000, LrcLine::new(300, "see you <i>soon</i>")),
+            (6000, LrcLine::new(5000, "bye")),
+        ];
+        let texts: Vec<(u64, u64, String)> = dedupe(lines)
+            .into_iter()
+            .map(|(end, line)| (line.time, end, line.text))
+            .collect();
+        assert_eq!(
+            texts,
+            [
+                (0, 2000, "see you soon".to_string()),
+                (5000, 6000, "bye".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn lyrics_cleaner_needs_timed_lines() {
+        assert!(lyrics_cleaner("no timing here").is_err());
+        assert_eq!(
+            lyrics_cleaner("[00:01.00]la la\n[00:01.20]la la\n[00:05.00]next").unwrap(),
+            "[00:01.00]la la\n[00:05.00]next"
+        );
+    }
+
+    /// A tenth of a second of silent 8 kHz mono PCM
+    fn silent_wav(path: &Path) {
+        let samples = 800u32;
+        let mut wav = b"RIFF".to_vec();
+        wav.extend((36 + samples * 2).to_le_bytes());
+        wav.extend(b"WAVEfmt ");
+        wav.extend(16u32.to_le_bytes());
+        wav.extend(1u16.to_le_bytes());
+        wav.extend(1u16.to_le_bytes());
+        wav.extend(8000u32.to_le_bytes());
+        wav.extend(16000u32.to_le_bytes());
+        wav.extend(2u16.to_le_bytes());
+        wav.extend(16u16.to_le_bytes());
+        wav.extend(b"data");
+        wav.extend((samples * 2).to_le_bytes());
+        wav.extend(vec![0; samples as usize * 2]);
+        fs::write(path, wav).unwrap();
+    }
+
+    #[test]
+    fn clear_removes_synced_lyrics() {
+        let music_file =
+            std::env::temp_dir().join(format!("azulbox-clear-{}.wav", std::process::id()));
+        silent_wav(&music_file);
+        embed(
+            &music_file,
+            "[00:01.00]Hello\n[00:02.00]Bye",
+            Some("en"),
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            read(&music_file).unwrap().as_deref(),
+            Some("[00:01.00]Hello\n[00:02.00]Bye")
+        );
+        clear(&music_file).unwrap();
+        let lyrics = read(&music_file).unwrap();
+        fs::remove_file(&music_file).unwrap();
+        assert_eq!(lyrics, None);
+    }
 }
@@ -0,0 +1,314 @@
+use crate::app::cores::lrc::Lrc;
+use crate::app::cores::translate::{TranslationMode, Translator, interleave, translate_lyrics};
+use crate::app::cores::{language, lyrics};
+use lofty::{prelude::*, probe::Probe};
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+/// Sources asked for lyrics when the config does not say otherwise, first one wins
+pub const DEFAULT_ORDER: &[&str] = &["kugou", "lrclib", "youtube"];
+
+/// A candidate scoring this much is used right away, the next sources are not asked
+const GOOD_MATCH: f32 = 0.8;
+/// Below this a candidate is likely another song and is never used
+const MIN_MATCH: f32 = 0.5;
+/// Seconds a candidate may differ from the track when the config does not say otherwise
+pub const DEFAULT_DURATION_TOLERANCE: u32 = 5;
+/// Score taken per second of difference with the track, so the closest version wins
+const DURATION_PENALTY: f32 = 0.01;
+
+/// What is known about the track the lyrics are searched for
+#[derive(Debug, Clone)]
+pub struct Query {
+    pub title: String,
+    pub artist: String,
+    pub duration: Option<f64>,
+    /// Seconds a candidate may differ from the track before it counts as another version,
+    /// 0 accepts any length
+    pub duration_tolerance: u32,
+}
+
+impl Query {
+    pub fn from_file(music_file: &Path, duration_tolerance: u32) -> Result<Self, Box<dyn Error>> {
+        let tagged_file = Probe::open(music_file)?.read()?;
+        let duration = tagged_file.properties().duration().as_secs_f64();
+        let tag = tagged_file.primary_tag().or(tagged_file.first_tag());
+        let (title, artist) = match tag.and_then(|tag| tag.title()) {
+            Some(title) => (
+                title.to_string(),
+                tag.and_then(|tag| tag.artist())
+                    .map(|a| a.to_string())
+                    .unwrap_or_default(),
+            ),
+            // Untagged files are often named `Artist - Title`
+            None => {
+                let stem = music_file
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .ok_or("Fail to open tag title")?;
+                match stem.split_once(" - ") {
+                    Some((artist, title)) => (title.trim().to_string(), artist.trim().to_string()),
+                    None => (stem.to_string(), String::new()),
+                }
+            }
+        };
+        Ok(Self {
+            title,
+            artist,
+            duration: (duration > 0.0).then_some(duration),
+            duration_tolerance,
+        })
+    }
+
+    /// Seconds between the track and a found length, when both are known
+    pub fn duration_gap(&self, found: Option<f64>) -> Option<f64> {
+        Some((self.duration? - found?).abs())
+    }
+
+    /// Whether a found length can be the same recording as the track
+    pub fn accepts(&self, found: Option<f64>) -> bool {
+        self.duration_tolerance == 0
+            || self
+                .duration_gap(found)
+                .is_none_or(|gap| gap <= self.duration_tolerance as f64)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub source: &'static str,
+    pub lyrics: String,
+    pub synced: bool,
+    /// Language of the lyrics when the source knows it, otherwise they get translated
+    pub lang: Option<String>,
+    pub duration: Option<f64>,
+    /// How well the candidate matches the query, from 0 to 1
+    pub score: f32,
+}
+
+impl Candidate {
+    /// Reject a candidate made for a track of another length, live and remixed versions
+    /// have lyrics timed for them, and rank the others by how close their length is
+    fn check_duration(mut self, query: &Query) -> Option<Self> {
+        let Some(gap) = query.duration_gap(self.duration) else {
+            return Some(self);
+        };
+        if !query.accepts(self.duration) {
+            log::info!(
+                "{} lyrics are {gap:.0}s off the track length, rejected",
+                self.source
+            );
+            return None;
+        }
+        self.score -= (gap as f32 * DURATION_PENALTY).min(0.1);
+        Some(self)
+    }
+
+    fn rank(&self) -> f32 {
+        // Synced lyrics win a close call against plain ones
+        if self.synced {
+            self.score + 0.05
+        } else {
+            self.score
+        }
+    }
+}
+
+pub trait LyricsProvider {
+    fn name(&self) -> &'static str;
+    fn search(&self, query: &Query) -> Result<Vec<Candidate>, Box<dyn Error>>;
+}
+
+/// Similarity of a found title and artist with the query
+pub fn text_score(query: &Query, title: &str, artist: &str) -> f32 {
+    let title_score =
+        textdistance::nstr::cosine(&query.title.to_lowercase(), &title.to_lowercase()) as f32;
+    if query.artist.is_empty() || artist.is_empty() {
+        return title_score;
+    }
+    let artist_score =
+        textdistance::nstr::cosine(&query.artist.to_lowercase(), &artist.to_lowercase()) as f32;
+    title_score * 0.7 + artist_score * 0.3
+}
+
+/// Ask the providers in order and stop at the first good match,
+/// otherwise keep the best candidate seen
+pub fn find(providers: &[Box<dyn LyricsProvider>], query: &Query) -> Option<Candidate> {
+    let mut best: Option<Candidate> = None;
+    for provider in providers {
+        let candidates = match provider.search(query) {
+            Ok(candidates) => candidates,
+            Err(e) => {
+                log::error!("Fail to get lyrics from {}: {e}", provider.name());
+                continue;
+            }
+        };
+        let Some(top) = candidates
+            .into_iter()
+            .filter(|candidate| !candidate.lyrics.trim().is_empty())
+            .filter_map(|candidate| candidate.check_duration(query))
+            .max_by(|a, b| a.rank().partial_cmp(&b.rank()).unwrap_or(Ordering::Equal))
+        else {
+            log::info!("No lyrics from {}", provider.name());
+            continue;
+        };
+        log::info!(
+            "Lyrics from {} score {:.2} synced {}",
+            top.source,
+            top.score,
+            top.synced
+        );
+        if top.score >= GOOD_MATCH {
+            return Some(top);
+        }
+        if top.score >= MIN_MATCH && best.as_ref().is_none_or(|best| top.rank() > best.rank()) {
+            best = Some(top);
+        }
+    }
+    best
+}
+
+/// Find the lyrics for `music_file`, translate them when needed and write the tag once
+pub fn apply(
+    providers: &[Box<dyn LyricsProvider>],
+    music_file: &Path,
+    lang_code: &str,
+    mode: TranslationMode,
+    translator: &Translator,
+    keep_lrc: bool,
+    duration_tolerance: u32,
+) -> Result<(), Box<dyn Error>> {
+    let query = Query::from_file(music_file, duration_tolerance)?;
+    let candidate = find(providers, &query).ok_or("No lyrics found")?;
+    let source = candidate.source;
+    let (lyrics, lang) = translate_candidate(candidate, music_file, lang_code, mode, translator)?;
+    lyrics::embed(music_file, &lyrics, lang.as_deref(), keep_lrc)?;
+    log::info!("Lyrics from {source} embedded");
+    Ok(())
+}
+
+/// The lyrics to write for a candidate and their language, translated as `mode` asks
+pub fn translate_candidate(
+    candidate: Candidate,
+    music_file: &Path,
+    lang_code: &str,
+    mode: TranslationMode,
+    translator: &Translator,
+) -> Result<(String, Option<String>), Box<dyn Error>> {
+    let mut lyrics = candidate.lyrics;
+    let detected = candidate.lang.or_else(|| language::detect(&lyrics));
+    log::info!("Lyrics language: {detected:?}");
+    // Language of what ends up in the tag
+    let mut embedded_lang = detected.clone();
+    if mode == TranslationMode::Off {
+        log::info!("Lyrics kept untranslated");
+    } else if detected
+        .as_deref()
+        .is_some_and(|detected| language::same(detected, lang_code))
+    {
+        log::info!("Lyrics already in {lang_code}, no translation needed");
+    } else if Lrc::parse(&lyrics).has_words() {
+        // Translated words would no longer line up with their timing
+        log::info!("Word-synced lyrics are kept untranslated");
+    } else {
+        match translate_lyrics(translator, lang_code, &lyrics) {
+            Ok(translated) => match mode {
+                TranslationMode::Replace => {
+                    lyrics = translated;
+                    embedded_lang = Some(lang_code.to_string());
+                }
+                TranslationMode::Interleave => lyrics = interleave(&lyrics, &translated),
+                TranslationMode::Sidecar => {
+                    let sidecar = lyrics::sidecar_path(music_file, lang_code);
+                    fs::write(&sidecar, translated)?;
+                    log::info!("Written translation to {sidecar:?}");
+                }
+                TranslationMode::Off => {}
+            },
+            // The original is still better than nothing
+            Err(e) => log::error!("Fail to translate lyrics: {e}"),
+        }
+    }
+    Ok((lyrics, embedded_lang))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(duration: Option<f64>, duration_tolerance: u32) -> Query {
+        Query {
+            title: "Song".into(),
+            artist: "Band".into(),
+            duration,
+            duration_tolerance,
+        }
+    }
+
+    fn candidate(source: &'static str, duration: Option<f64>, score: f32) -> Candidate {
+        Candidate {
+            source,
+            lyrics: "[00:01.00]La".into(),
+            synced: true,
+            lang: None,
+            duration,
+            score,
+        }
+    }
+
+    struct Fixed(&'static str, Vec<Candidate>);
+
+    impl LyricsProvider for Fixed {
+        fn name(&self) -> &'static str {
+            self.0
+        }
+        fn search(&self, _: &Query) -> Result<Vec<Candidate>, Box<dyn Error>> {
+            Ok(self.1.clone())
+        }
+    }
+
+    #[test]
+    fn accepts_within_tolerance() {
+        let query = query(Some(200.0), 5);
+        assert!(query.accepts(Some(205.0)));
+        assert!(query.accepts(Some(195.5)));
+        assert!(!query.accepts(Some(206.0)));
+        // Nothing to compare
+        assert!(query.accepts(None));
+    }
+
+    #[test]
+    fn zero_tolerance_accepts_any_length() {
+        assert!(query(Some(200.0), 0).accepts(Some(400.0)));
+    }
+
+    #[test]
+    fn unknown_track_length_accepts_all() {
+        assert!(query(None, 5).accepts(Some(400.0)));
+    }
+
+    #[test]
+    fn find_rejects_other_versions_and_prefers_the_closest() {
+        let providers: Vec<Box<dyn LyricsProvider>> = vec![
+            Box::new(Fixed("live", vec![candidate("live", Some(260.0), 1.0)])),
+            Box::new(Fixed(
+                "studio",
+                vec![
+                    candidate("far", Some(204.0), 0.9),
+                    candidate("near", Some(200.5), 0.9),
+                ],
+            )),
+        ];
+        let found = find(&providers, &query(Some(200.0), 5)).unwrap();
+        assert_eq!(found.source, "near");
+    }
+
+    #[test]
+    fn find_skips_weak_matches() {
+        let providers: Vec<Box<dyn LyricsProvider>> =
+            vec![Box::new(Fixed("weak", vec![candidate("weak", None, 0.3)]))];
+        assert!(find(&providers, &query(Some(200.0), 5)).is_none());
+    }
+}
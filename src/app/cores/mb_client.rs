@@ -0,0 +1,106 @@
+use serde::de::DeserializeOwned;
+use std::error::Error;
+use std::sync::{LazyLock, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use ureq::{Agent, Body};
+
+const USER_AGENT: &str = "Azulbox (https://github.com/tahosol/azul-box)";
+const MUSICBRAINZ: &str = "https://musicbrainz.org/";
+/// MusicBrainz allows one request per second, the Cover Art Archive has no such limit
+const INTERVAL: Duration = Duration::from_secs(1);
+const RETRIES: u32 = 4;
+/// Doubled after every failed attempt
+const BACKOFF: Duration = Duration::from_secs(1);
+
+/// Api answers are small, a server taking longer than this is stuck
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// One agent and one pace for every job, playlists tagged side by side share the limit
+static CLIENT: LazyLock<Client> = LazyLock::new(|| Client {
+    agent: Agent::config_builder()
+        .timeout_global(Some(TIMEOUT))
+        .build()
+        .into(),
+    // Full size covers are often several MB, only the wait for the server is bounded
+    downloads: Agent::config_builder()
+        .timeout_connect(Some(TIMEOUT))
+        .timeout_recv_response(Some(TIMEOUT))
+        .build()
+        .into(),
+    next: Mutex::new(Instant::now()),
+});
+
+pub struct Client {
+    agent: Agent,
+    downloads: Agent,
+    /// When the next MusicBrainz request may go out
+    next: Mutex<Instant>,
+}
+
+pub fn client() -> &'static Client {
+    &CLIENT
+}
+
+impl Client {
+    pub fn json<T: DeserializeOwned>(&self, url: &str) -> Result<T, Box<dyn Error>> {
+        self.get(&self.agent, url, |body| body.read_json::<T>())
+    }
+
+    pub fn bytes(&self, url: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+        self.get(&self.downloads, url, |body| body.read_to_vec())
+    }
+
+    /// The body is read inside the retry, a connection dropped halfway is tried again too
+    fn get<T>(
+        &self,
+        agent: &Agent,
+        url: &str,
+        read: impl Fn(&mut Body) -> Result<T, ureq::Error>,
+    ) -> Result<T, Box<dyn Error>> {
+        let mut backoff = BACKOFF;
+        let mut attempt = 1;
+        loop {
+            if url.starts_with(MUSICBRAINZ) {
+                self.wait();
+            }
+            let result = agent
+                .get(url)
+                .header("User-Agent", USER_AGENT)
+                .call()
+                .and_then(|mut response| read(response.body_mut()));
+            match result {
+                Ok(value) => return Ok(value),
+                Err(e) if attempt < RETRIES && retryable(&e) => {
+                    log::warn!("Fail {url} ({e}), retry {attempt} in {backoff:?}");
+                    thread::sleep(backoff);
+                    backoff *= 2;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    fn wait(&self) {
+        let mut next = self.next.lock().unwrap();
+        let now = Instant::now();
+        if *next > now {
+            thread::sleep(*next - now);
+        }
+        *next = Instant::now() + INTERVAL;
+    }
+}
+
+/// Busy servers and network trouble, a 404 will still be a 404 later
+fn retryable(e: &ureq::Error) -> bool {
+    matches!(
+        e,
+        ureq::Error::StatusCode(429 | 502 | 503 | 504)
+            | ureq::Error::Io(_)
+            | ureq::Error::Timeout(_)
+            | ureq::Error::HostNotFound
+            | ureq::Error::ConnectionFailed
+            | ureq::Error::BodyStalled
+    )
+}
@@ -1,14 +1,26 @@
+pub mod acoustid;
 pub mod config;
 pub mod cover;
 pub mod depen_manager;
 pub mod files;
 pub mod kugou;
+pub mod language;
+pub mod library;
 
+pub mod lrc;
 pub mod lrclib;
 pub mod lyrics;
+pub mod lyrics_provider;
+pub mod mb_client;
 pub mod musicbrainz;
 pub mod notify;
+pub mod player;
+pub mod process;
+pub mod progress;
+pub mod queue;
 pub mod string_cleaner;
+pub mod subtitle;
+pub mod tags;
 pub mod translate;
 pub mod url_checker;
 pub mod ytdlp;
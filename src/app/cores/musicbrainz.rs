@@ -1,35 +1,229 @@
 use lofty::config::WriteOptions;
+use lofty::id3::v2::{Frame, Id3v2Tag, UniqueFileIdentifierFrame};
 use lofty::picture::{MimeType, Picture, PictureType};
 use lofty::prelude::*;
 use lofty::probe::Probe;
-use lofty::tag::Tag;
 use lofty::tag::items::Timestamp;
+use lofty::tag::{ItemValue, Tag, TagItem, TagType};
 
 use std::error::Error;
+use std::fmt;
 use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
 use std::time::Duration;
-use ureq::Agent;
 
-use crate::app::cores::string_cleaner;
+/// Folksonomy tags kept as genres when the recording has no genre
+const MAX_TAGS: usize = 5;
+/// Search results looked at, and releases of them shown in the review
+const MAX_RECORDINGS: usize = 5;
+const MAX_CANDIDATES: usize = 12;
+const ID3V2_IDS: [(ItemKey, &str); 3] = [
+    (ItemKey::MusicBrainzReleaseId, "MusicBrainz Album Id"),
+    (
+        ItemKey::MusicBrainzReleaseGroupId,
+        "MusicBrainz Release Group Id",
+    ),
+    (ItemKey::MusicBrainzTrackId, "MusicBrainz Release Track Id"),
+];
 
-pub fn work(opt: &Path, similarity_rate: i8) -> Result<(), Box<dyn Error>> {
-    let mut tagged_file = Probe::open(opt)?.read()?;
+use crate::app::cores::acoustid::Acoustid;
+use crate::app::cores::files::writable_tag;
+use crate::app::cores::mb_client::client;
+use crate::app::cores::process::ProcessHandle;
+use crate::app::cores::{cover, string_cleaner};
+
+/// What tagging one file came to, errors are returned on their own
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Matched { cover: bool },
+    NotFound,
+    Skipped,
+}
+
+impl Outcome {
+    pub fn is_matched(&self) -> bool {
+        matches!(self, Outcome::Matched { .. })
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::Matched { cover: true } => write!(f, "Matched with cover"),
+            Outcome::Matched { cover: false } => write!(f, "Matched without cover"),
+            Outcome::NotFound => write!(f, "Not found"),
+            Outcome::Skipped => write!(f, "Skipped"),
+        }
+    }
+}
 
-    let tag = match tagged_file.primary_tag_mut() {
-        Some(primary_tag) => primary_tag,
+/// One release of one recording, what the review lists
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub recording_id: String,
+    pub title: String,
+    pub artist: String,
+    pub score: i8,
+    pub release_id: String,
+    pub album: String,
+    pub date: Option<String>,
+    pub country: Option<String>,
+    /// Release group type and status, like `Album, Compilation`
+    pub kind: String,
+    /// Small front cover from the Cover Art Archive
+    pub cover: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Choice {
+    Pick(usize),
+    Skip,
+    /// Skip this track and the rest of the playlist
+    SkipAll,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Remembered {
+    Release(String),
+    Skip,
+}
+
+/// Shared between a music job and the review window, the job waits for `choice`
+#[derive(Debug, Default)]
+pub struct Review {
+    /// Name of the file waiting for the user
+    pub file: Option<String>,
+    pub candidates: Vec<Candidate>,
+    pub choice: Option<Choice>,
+    /// Last choice, for the next tracks of the playlist
+    pub remembered: Option<Remembered>,
+}
+
+pub type SharedReview = Arc<Mutex<Review>>;
+
+pub fn work(
+    opt: &Path,
+    similarity_rate: i8,
+    acoustid: Option<&Acoustid>,
+) -> Result<Outcome, Box<dyn Error>> {
+    let mut tagged_file = Probe::open(opt)?.read()?;
+    let tag = writable_tag(&mut tagged_file).ok_or("Fail to open tag")?;
+    let candidates = identify(opt, tag, acoustid)?;
+    match candidates
+        .into_iter()
+        .next()
+        .filter(|candidate| candidate.score > similarity_rate)
+    {
+        Some(candidate) => apply(opt, tag, &candidate),
         None => {
-            if let Some(first_tag) = tagged_file.first_tag_mut() {
-                first_tag
-            } else {
-                let tag_type = tagged_file.primary_tag_type();
+            log::error!("Fail To Find Musicbrainz Data");
+            Ok(Outcome::NotFound)
+        }
+    }
+}
 
-                log::warn!("No tags found, creating a new tag of type `{tag_type:?}`");
-                tagged_file.insert_tag(Tag::new(tag_type));
+/// Let the user pick the match in the Music-dl window, a picked release is used again
+/// for the next tracks of the playlist when it has them
+pub fn review(
+    opt: &Path,
+    acoustid: Option<&Acoustid>,
+    review: &SharedReview,
+    process: &ProcessHandle,
+) -> Result<Outcome, Box<dyn Error>> {
+    let remembered = review.lock().unwrap().remembered.clone();
+    if remembered == Some(Remembered::Skip) {
+        log::info!("Musicbrainz skipped for the playlist");
+        return Ok(Outcome::Skipped);
+    }
+    let mut tagged_file = Probe::open(opt)?.read()?;
+    let tag = writable_tag(&mut tagged_file).ok_or("Fail to open tag")?;
+    let mut candidates = identify(opt, tag, acoustid)?;
+    if candidates.is_empty() {
+        log::error!("Fail To Find Musicbrainz Data");
+        return Ok(Outcome::NotFound);
+    }
+    if let Some(Remembered::Release(release_id)) = &remembered
+        && let Some(candidate) = candidates
+            .iter()
+            .find(|candidate| &candidate.release_id == release_id)
+    {
+        log::info!("Using the release picked before {release_id}");
+        return apply(opt, tag, candidate);
+    }
 
-                tagged_file.primary_tag_mut().ok_or("Fail to open tag")?
-            }
+    for candidate in candidates.iter_mut() {
+        candidate.cover = thumbnail(&candidate.release_id).ok();
+    }
+    {
+        let mut review = review.lock().unwrap();
+        review.file = opt
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned());
+        review.candidates = candidates.clone();
+        review.choice = None;
+    }
+    let choice = loop {
+        if let Err(e) = process.check() {
+            *review.lock().unwrap() = Review::default();
+            return Err(e);
+        }
+        if let Some(choice) = review.lock().unwrap().choice.take() {
+            break choice;
         }
+        thread::sleep(Duration::from_millis(200));
     };
+    let mut review = review.lock().unwrap();
+    review.file = None;
+    review.candidates.clear();
+    match choice {
+        Choice::Pick(index) => {
+            let candidate = candidates.get(index).ok_or("No such candidate")?;
+            review.remembered = Some(Remembered::Release(candidate.release_id.clone()));
+            drop(review);
+            apply(opt, tag, candidate)
+        }
+        Choice::Skip => Ok(Outcome::Skipped),
+        Choice::SkipAll => {
+            review.remembered = Some(Remembered::Skip);
+            Ok(Outcome::Skipped)
+        }
+    }
+}
+
+/// Recordings that sound like `opt`, the title search only when the fingerprint finds nothing
+fn identify(
+    opt: &Path,
+    tag: &Tag,
+    acoustid: Option<&Acoustid>,
+) -> Result<Vec<Candidate>, Box<dyn Error>> {
+    if let Some(acoustid) = acoustid {
+        match fingerprint_match(opt, acoustid) {
+            Ok(candidates) if !candidates.is_empty() => return Ok(candidates),
+            Ok(_) => log::info!("No AcoustID match, searching by title"),
+            Err(e) => log::error!("Fail acoustid {e}"),
+        }
+    }
+    search(tag)
+}
+
+fn fingerprint_match(opt: &Path, acoustid: &Acoustid) -> Result<Vec<Candidate>, Box<dyn Error>> {
+    let mut candidates = vec![];
+    for found in acoustid.identify(opt)?.into_iter().take(MAX_RECORDINGS) {
+        let query = format!(
+            "https://musicbrainz.org/ws/2/recording/{}?inc=artist-credits+releases+release-groups&fmt=json",
+            found.recording_id
+        );
+        log::info!("AcoustID recording: {query}");
+        let mut record = client().json::<Recording>(&query)?;
+        record.score = (found.score * 100.0).round() as i8;
+        candidates.extend(releases_of(&record));
+    }
+    Ok(ranked(candidates))
+}
+
+/// Every release of the best recordings found by title and artist
+fn search(tag: &Tag) -> Result<Vec<Candidate>, Box<dyn Error>> {
     use url::form_urlencoded;
 
     let artist = tag.artist().ok_or("Fail artist tag")?;
@@ -43,137 +237,326 @@ pub fn work(opt: &Path, similarity_rate: i8) -> Result<(), Box<dyn Error>> {
         title, artist
     );
     log::info!("musicbrain_work query: {query}");
-    let _ = fetch_musicbrainzapi(&query, opt, similarity_rate, tag);
-    Ok(())
+    let resp = client().json::<ApiResponseMusicBrainz>(&query)?;
+
+    Ok(ranked(
+        resp.recordings
+            .iter()
+            .take(MAX_RECORDINGS)
+            .flat_map(releases_of)
+            .collect(),
+    ))
 }
-fn fetch_musicbrainzapi(
-    q: &str,
-    opt: &Path,
-    similarity_rate: i8,
-    tag: &mut Tag,
-) -> Result<(), Box<dyn Error>> {
-    let config = Agent::config_builder()
-        .timeout_global(Some(Duration::from_secs(5)))
-        .build();
 
-    let agent: Agent = config.into();
-    let resp = agent
-        .get(q)
-        .header(
-            "User-Agent",
-            "Azulbox (https://github.com/tahosol/azul-box)",
-        )
-        .call()?
-        .body_mut()
-        .read_json::<ApiResponseMusicBrainz>()?;
-    if !resp.recordings.is_empty() && (resp.recordings[0].score > similarity_rate) {
-        let record = resp.recordings[0].clone();
-        log::info!("Record ID: {}", record.id);
-        log::info!("Record Title: {}", record.title);
-        tag.set_title(record.title);
-        let query_with_id = format!(
-            "https://musicbrainz.org/ws/2/recording/{}?inc=artist-credits+isrcs+releases+release-groups+discids&fmt=json",
-            record.id
-        );
-        log::info!("Query_with_id: {query_with_id}");
-        let mut re_for_id = agent
-            .get(query_with_id)
-            .header(
-                "User-Agent",
-                "Azulbox (https://github.com/tahosol/azul-box)",
-            )
-            .call()?;
-        let data = re_for_id.body_mut().read_json::<IDAPI>()?;
-        if let Some(artists) = data.artist_credit {
-            log::info!("Artist: {}", artists[0].name);
-            tag.set_artist(artists[0].name.clone());
+/// Releases of `record` with their rank, official studio albums first
+fn releases_of(record: &Recording) -> Vec<(u8, String, Candidate)> {
+    let artist = record
+        .artist_credit
+        .as_deref()
+        .map(credit_name)
+        .unwrap_or_default();
+    let mut candidates = vec![];
+    for release in record.releases.iter().flatten() {
+        let group = release.release_group.as_ref();
+        let secondary = group
+            .map(|group| group.secondary_types.clone())
+            .unwrap_or_default();
+        let primary = group.and_then(|group| group.primary_type.clone());
+        let status = release.status.clone();
+        let rank = u8::from(status.as_deref() != Some("Official")) * 4
+            + u8::from(!secondary.is_empty()) * 2
+            + u8::from(primary.as_deref() != Some("Album"));
+        let kind = primary
+            .into_iter()
+            .chain(secondary)
+            .chain(status.filter(|status| status != "Official"))
+            .collect::<Vec<String>>()
+            .join(", ");
+        candidates.push((
+            rank,
+            release.date.clone().unwrap_or("9999".into()),
+            Candidate {
+                recording_id: record.id.clone(),
+                title: record.title.clone(),
+                artist: artist.clone(),
+                score: record.score,
+                release_id: release.id.clone(),
+                album: release.title.clone(),
+                date: release.date.clone().filter(|date| !date.is_empty()),
+                country: release.country.clone(),
+                kind,
+                cover: None,
+            },
+        ));
+    }
+    candidates
+}
+
+/// Best first: the score of the recording, then official studio albums before singles,
+/// compilations and bootlegs, then the oldest
+fn ranked(mut candidates: Vec<(u8, String, Candidate)>) -> Vec<Candidate> {
+    candidates.sort_by(|a, b| {
+        b.2.score
+            .cmp(&a.2.score)
+            .then(a.0.cmp(&b.0))
+            .then(a.1.cmp(&b.1))
+    });
+    candidates
+        .into_iter()
+        .take(MAX_CANDIDATES)
+        .map(|(_, _, candidate)| candidate)
+        .collect()
+}
+
+fn thumbnail(release_id: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    client().bytes(&format!(
+        "https://coverartarchive.org/release/{release_id}/front-250"
+    ))
+}
+
+/// Tag `opt` with the recording and release of `candidate`, then its cover
+fn apply(opt: &Path, tag: &mut Tag, candidate: &Candidate) -> Result<Outcome, Box<dyn Error>> {
+    log::info!("Record ID: {}", candidate.recording_id);
+    log::info!("Record Title: {}", candidate.title);
+    tag.set_title(candidate.title.clone());
+    let query_with_id = format!(
+        "https://musicbrainz.org/ws/2/recording/{}?inc=artist-credits+isrcs+releases+release-groups+genres+tags&fmt=json",
+        candidate.recording_id
+    );
+    log::info!("Query_with_id: {query_with_id}");
+    let data = client().json::<IDAPI>(&query_with_id)?;
+    set_id(
+        tag,
+        ItemKey::MusicBrainzRecordingId,
+        candidate.recording_id.clone(),
+    );
+    if let Some(artists) = &data.artist_credit {
+        let artist = credit_name(artists);
+        log::info!("Artist: {artist}");
+        tag.set_artist(artist);
+        set_artist_ids(tag, ItemKey::MusicBrainzArtistId, artists);
+    }
+    if let Some(isrc) = data.isrcs.as_ref().and_then(|isrcs| isrcs.first()) {
+        log::info!("ISRCS: {isrc}");
+        tag.insert_text(ItemKey::Isrc, isrc.clone());
+    }
+    let release_id = &candidate.release_id;
+    // The recording lookup only lists the track of this recording, no label
+    let release = match fetch_release(release_id) {
+        Ok(release) => release,
+        Err(e) => {
+            log::error!("Fail to fetch release {e}");
+            data.releases
+                .iter()
+                .flatten()
+                .find(|release| &release.id == release_id)
+                .cloned()
+                .ok_or("Fail To Find Releases Data")?
         }
-        if let Some(isrcs) = data.isrcs {
-            if !isrcs.is_empty() {
-                log::info!("ISRCS: {}", isrcs[0]);
-                tag.insert_text(ItemKey::Isrc, isrcs[0].clone());
-            }
+    };
+    if let Some(date) = &release.date {
+        if let Some(year) = date
+            .split("-")
+            .next()
+            .and_then(|years| years.parse::<u16>().ok())
+        {
+            tag.set_date(Timestamp {
+                year,
+                hour: None,
+                day: None,
+                month: None,
+                second: None,
+                minute: None,
+            });
         }
-        if let Some(releases) = data.releases {
-            if !releases.is_empty() {
-                let release_id = &releases[0].id;
-                if let Some(date) = &releases[0].date {
-                    let years = &date
-                        .split("-")
-                        .next()
-                        .ok_or("Fail to get years from release")?;
-                    let year: u16 = years.parse::<u16>()?;
-                    tag.set_date(Timestamp {
-                        year,
-                        hour: None,
-                        day: None,
-                        month: None,
-                        second: None,
-                        minute: None,
-                    });
-                    tag.insert_text(ItemKey::ReleaseDate, date.clone());
-                }
-                tag.set_album(releases[0].title.clone());
-                if let Some(media) = &releases[0].media {
-                    tag.set_disk(media[0].position);
-                    tag.set_track(media[0].position);
-                    tag.set_track_total(media[0].track_count);
-                    tag.set_disk_total(media[0].track_count);
-                }
-
-                log::info!("Release ID: {release_id}");
-                if tag.save_to_path(opt, WriteOptions::default()).is_ok() {
-                    log::info!("Musicbrainz Metadata Embedded Success");
-                } else {
-                    log::error!("Fail To Embed Metadata From MusicBrainz");
-                }
-                let que = format!("https://coverartarchive.org/release/{}", release_id);
-                log::info!("Cover Art Link: {que}");
-                let mut res = agent
-                    .get(que)
-                    .header(
-                        "User-Agent",
-                        "Azulbox (https://github.com/tahosol/azul-box)",
-                    )
-                    .call()?;
-                let callfocover = res.body_mut().read_json::<ApiResponseCover>()?;
-                if let Some(images) = callfocover.images {
-                    log::info!("{}", images[0].image);
-                    let img_req = agent
-                        .get(&images[0].image)
-                        .header(
-                            "User-Agent",
-                            "Azulbox (https://github.com/tahosol/azul-box)",
-                        )
-                        .call()?;
-                    let data: Vec<u8> = img_req.into_body().read_to_vec()?;
-
-                    let picture = Picture::unchecked(data)
-                        .mime_type(MimeType::Jpeg)
-                        .pic_type(PictureType::CoverFront)
-                        .build();
-                    log::info!("Cover Image Found!");
-                    if tag.picture_count() > 0 {
-                        tag.remove_picture(0);
-                    }
-                    tag.push_picture(picture);
-                    if tag.save_to_path(opt, WriteOptions::default()).is_ok() {
-                        log::info!("Musicbrainz Cover Embedded Success");
-                    } else {
-                        log::error!("Fail To Embed Cover From MusicBrainz");
-                    }
-                } else {
-                    log::error!("Fail To Find Cover Art");
-                }
-            } else {
-                log::error!("Fail To Find Releases Data");
-            }
+        tag.insert_text(ItemKey::ReleaseDate, date.clone());
+    }
+    tag.set_album(release.title.clone());
+    set_id(tag, ItemKey::MusicBrainzReleaseId, release.id.clone());
+    set_release(tag, &release, &candidate.recording_id);
+    set_genres(tag, &data, &release);
+
+    log::info!("Release ID: {release_id}");
+    save_tag(tag, opt)?;
+    log::info!("Musicbrainz Metadata Embedded Success");
+
+    let data = match front_cover(release_id) {
+        Ok(data) => data,
+        Err(e) => {
+            log::error!("Fail To Find Cover Art {e}");
+            return Ok(Outcome::Matched { cover: false });
         }
+    };
+    let picture = Picture::unchecked(data.clone())
+        .mime_type(MimeType::Jpeg)
+        .pic_type(PictureType::CoverFront)
+        .build();
+    log::info!("Cover Image Found!");
+    if tag.picture_count() > 0 {
+        tag.remove_picture(0);
+    }
+    tag.push_picture(picture);
+    if save_tag(tag, opt).is_ok() {
+        log::info!("Musicbrainz Cover Embedded Success");
     } else {
-        log::error!("Fail To Find Musicbrainz Data");
+        log::error!("Fail To Embed Cover From MusicBrainz");
+        cover::write_sidecar(&data, opt);
     }
+    Ok(Outcome::Matched { cover: true })
+}
+
+fn front_cover(release_id: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let que = format!("https://coverartarchive.org/release/{}", release_id);
+    log::info!("Cover Art Link: {que}");
+    let images = client()
+        .json::<ApiResponseCover>(&que)?
+        .images
+        .ok_or("No cover art")?;
+    let image = images.first().ok_or("No cover art")?;
+    log::info!("{}", image.image);
+    client().bytes(&image.image)
+}
+
+/// Full release with its track listing, labels and release group
+fn fetch_release(release_id: &str) -> Result<Release, Box<dyn Error>> {
+    let query = format!(
+        "https://musicbrainz.org/ws/2/release/{release_id}?inc=artist-credits+labels+recordings+release-groups+genres+tags&fmt=json"
+    );
+    log::info!("Release query: {query}");
+    client().json::<Release>(&query)
+}
+
+/// Kept even where lofty has no mapping, `save_tag` writes them for ID3v2
+fn set_id(tag: &mut Tag, key: ItemKey, id: String) {
+    tag.insert_unchecked(TagItem::new(key, ItemValue::Text(id)));
+}
+
+/// lofty leaves the single value MusicBrainz ids out of ID3v2, Picard writes them as
+/// TXXX frames and the recording as UFID
+pub fn save_tag(tag: &Tag, opt: &Path) -> Result<(), Box<dyn Error>> {
+    if tag.tag_type() != TagType::Id3v2 {
+        tag.save_to_path(opt, WriteOptions::default())?;
+        return Ok(());
+    }
+    let mut id3v2 = Id3v2Tag::from(tag.clone());
+    for (key, description) in ID3V2_IDS {
+        if let Some(id) = tag.get_string(key) {
+            id3v2.insert_user_text(description.to_string(), id.to_string());
+        }
+    }
+    if let Some(id) = tag.get_string(ItemKey::MusicBrainzRecordingId) {
+        id3v2.insert(Frame::UniqueFileIdentifier(UniqueFileIdentifierFrame::new(
+            "http://musicbrainz.org",
+            id.as_bytes().to_vec(),
+        )));
+    }
+    id3v2.save_to_path(opt, WriteOptions::default())?;
     Ok(())
 }
+
+/// Artists as credited, like `A feat. B`
+fn credit_name(credits: &[ArtistCredit]) -> String {
+    credits
+        .iter()
+        .map(|credit| format!("{}{}", credit.name, credit.joinphrase))
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+fn set_artist_ids(tag: &mut Tag, key: ItemKey, credits: &[ArtistCredit]) {
+    tag.remove_key(key);
+    for artist in credits.iter().filter_map(|credit| credit.artist.as_ref()) {
+        tag.push(TagItem::new(key, ItemValue::Text(artist.id.clone())));
+    }
+}
+
+/// Album artist, release group, label and where the recording sits in the release
+fn set_release(tag: &mut Tag, release: &Release, recording_id: &str) {
+    if let Some(artists) = &release.artist_credit {
+        tag.insert_text(ItemKey::AlbumArtist, credit_name(artists));
+        set_artist_ids(tag, ItemKey::MusicBrainzReleaseArtistId, artists);
+    }
+    if let Some(group) = &release.release_group {
+        set_id(tag, ItemKey::MusicBrainzReleaseGroupId, group.id.clone());
+        if let Some(date) = group.first_release_date.as_ref().filter(|d| !d.is_empty()) {
+            tag.insert_text(ItemKey::OriginalReleaseDate, date.clone());
+        }
+    }
+    if let Some(label_info) = release.label_info.as_ref().and_then(|info| info.first()) {
+        if let Some(label) = &label_info.label {
+            tag.insert_text(ItemKey::Label, label.name.clone());
+        }
+        if let Some(catalog) = &label_info.catalog_number {
+            tag.insert_text(ItemKey::CatalogNumber, catalog.clone());
+        }
+    }
+    let Some(media) = &release.media else {
+        return;
+    };
+    // Tracks of the recording lookup have no recording, they are the recording itself
+    let found = media.iter().find_map(|medium| {
+        medium
+            .tracks
+            .iter()
+            .flatten()
+            .find(|track| {
+                track
+                    .recording
+                    .as_ref()
+                    .is_none_or(|recording| recording.id == recording_id)
+            })
+            .map(|track| (medium, track))
+    });
+    let Some((medium, track)) = found else {
+        log::error!("Fail to find the track in the release");
+        return;
+    };
+    log::info!("Disc {} track {}", medium.position, track.position);
+    tag.set_disk(medium.position);
+    tag.set_disk_total(media.len() as u32);
+    tag.set_track(track.position);
+    tag.set_track_total(medium.track_count);
+    set_id(tag, ItemKey::MusicBrainzTrackId, track.id.clone());
+}
+
+/// Genres of the recording, release and release group, the top tags when nobody set a genre
+fn set_genres(tag: &mut Tag, recording: &IDAPI, release: &Release) {
+    let group = release.release_group.as_ref();
+    let collect = |pick: fn(&Folksonomy) -> &Option<Vec<Genre>>| {
+        let mut genres: Vec<&Genre> = [pick(&recording.folksonomy), pick(&release.folksonomy)]
+            .into_iter()
+            .chain(group.map(|group| pick(&group.folksonomy)))
+            .flatten()
+            .flatten()
+            .collect();
+        genres.sort_by_key(|genre| std::cmp::Reverse(genre.count));
+        let mut names: Vec<String> = vec![];
+        for genre in genres {
+            if !names
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case(&genre.name))
+            {
+                names.push(genre.name.clone());
+            }
+        }
+        names
+    };
+    let mut genres = collect(|f| &f.genres);
+    if genres.is_empty() {
+        genres = collect(|f| &f.tags);
+        genres.truncate(MAX_TAGS);
+    }
+    if genres.is_empty() {
+        return;
+    }
+    log::info!("Genres: {}", genres.join(", "));
+    tag.remove_key(ItemKey::Genre);
+    for genre in genres {
+        tag.push(TagItem::new(ItemKey::Genre, ItemValue::Text(genre)));
+    }
+}
+
 use serde::Deserialize;
 #[derive(Debug, Deserialize)]
 struct IDAPI {
@@ -181,6 +564,8 @@ struct IDAPI {
     artist_credit: Option<Vec<ArtistCredit>>,
     releases: Option<Vec<Release>>,
     isrcs: Option<Vec<String>>,
+    #[serde(flatten)]
+    folksonomy: Folksonomy,
 }
 
 #[derive(Debug, Deserialize)]
@@ -199,12 +584,24 @@ struct ApiResponseMusicBrainz {
 #[derive(Debug, Deserialize, Clone)]
 struct Recording {
     id: String,
+    /// Only search results have one
+    #[serde(default)]
     score: i8,
     title: String,
+    #[serde(rename = "artist-credit")]
+    artist_credit: Option<Vec<ArtistCredit>>,
+    releases: Option<Vec<Release>>,
 }
 #[derive(Debug, Deserialize, Clone)]
 struct ArtistCredit {
     name: String,
+    #[serde(default)]
+    joinphrase: String,
+    artist: Option<Artist>,
+}
+#[derive(Debug, Deserialize, Clone)]
+struct Artist {
+    id: String,
 }
 #[derive(Debug, Deserialize, Clone)]
 struct Release {
@@ -212,10 +609,129 @@ struct Release {
     title: String,
     media: Option<Vec<Media>>,
     date: Option<String>,
+    status: Option<String>,
+    country: Option<String>,
+    #[serde(rename = "artist-credit")]
+    artist_credit: Option<Vec<ArtistCredit>>,
+    #[serde(rename = "release-group")]
+    release_group: Option<ReleaseGroup>,
+    #[serde(rename = "label-info")]
+    label_info: Option<Vec<LabelInfo>>,
+    #[serde(flatten)]
+    folksonomy: Folksonomy,
+}
+#[derive(Debug, Deserialize, Clone)]
+struct ReleaseGroup {
+    id: String,
+    #[serde(rename = "first-release-date")]
+    first_release_date: Option<String>,
+    #[serde(rename = "primary-type")]
+    primary_type: Option<String>,
+    #[serde(rename = "secondary-types", default)]
+    secondary_types: Vec<String>,
+    #[serde(flatten)]
+    folksonomy: Folksonomy,
+}
+#[derive(Debug, Deserialize, Clone)]
+struct LabelInfo {
+    #[serde(rename = "catalog-number")]
+    catalog_number: Option<String>,
+    label: Option<Label>,
+}
+#[derive(Debug, Deserialize, Clone)]
+struct Label {
+    name: String,
 }
 #[derive(Debug, Deserialize, Clone)]
 struct Media {
     position: u32,
     #[serde(rename = "track-count")]
     track_count: u32,
+    /// `track` in a recording lookup, only the one of the recording
+    #[serde(alias = "track")]
+    tracks: Option<Vec<Track>>,
+}
+#[derive(Debug, Deserialize, Clone)]
+struct Track {
+    id: String,
+    position: u32,
+    recording: Option<RecordingId>,
+}
+#[derive(Debug, Deserialize, Clone)]
+struct RecordingId {
+    id: String,
+}
+/// Genres are the curated part of the tags users vote on
+#[derive(Debug, Deserialize, Clone, Default)]
+struct Folksonomy {
+    genres: Option<Vec<Genre>>,
+    tags: Option<Vec<Genre>>,
+}
+#[derive(Debug, Deserialize, Clone)]
+struct Genre {
+    name: String,
+    #[serde(default)]
+    count: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn release(id: &str, date: &str, status: &str, primary: &str, secondary: &[&str]) -> String {
+        format!(
+            r#"{{"id": "{id}", "title": "{id}", "date": "{date}", "status": "{status}",
+                "release-group": {{"id": "g", "primary-type": "{primary}",
+                "secondary-types": {secondary:?}}}}}"#
+        )
+    }
+
+    fn recording(id: &str, score: i8, releases: &[String]) -> Recording {
+        serde_json::from_str(&format!(
+            r#"{{"id": "{id}", "score": {score}, "title": "Song",
+                "artist-credit": [{{"name": "A", "joinphrase": " & "}}, {{"name": "B"}}],
+                "releases": [{}]}}"#,
+            releases.join(",")
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn ranked_prefers_score_then_studio_albums_then_oldest() {
+        let best = recording(
+            "best",
+            100,
+            &[
+                release("bootleg", "1990", "Bootleg", "Album", &[]),
+                release("compilation", "1995", "Official", "Album", &["Compilation"]),
+                release("single", "1998", "Official", "Single", &[]),
+                release("reissue", "2010", "Official", "Album", &[]),
+                release("album", "2000", "Official", "Album", &[]),
+            ],
+        );
+        let other = recording(
+            "other",
+            90,
+            &[release("other", "1980", "Official", "Album", &[])],
+        );
+        let candidates = ranked([&other, &best].into_iter().flat_map(releases_of).collect());
+        let order: Vec<&str> = candidates
+            .iter()
+            .map(|candidate| candidate.release_id.as_str())
+            .collect();
+        assert_eq!(
+            order,
+            [
+                "album",
+                "reissue",
+                "single",
+                "compilation",
+                "bootleg",
+                "other"
+            ]
+        );
+        assert_eq!(candidates[0].artist, "A & B");
+        assert_eq!(candidates[3].kind, "Album, Compilation");
+        assert_eq!(candidates[4].kind, "Album, Bootleg");
+    }
 }
@@ -0,0 +1,117 @@
+use rodio::{Decoder, DeviceSinkBuilder, MixerDeviceSink, Player};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// rodio is only built with Vorbis, every other format is turned into a temporary ogg first.
+/// Returns the file to play and whether it is a temporary one
+pub fn playable(
+    music_file: &Path,
+    ffmpeg: Option<PathBuf>,
+) -> Result<(PathBuf, bool), Box<dyn Error>> {
+    let ext = music_file
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    if ext == "ogg" || ext == "oga" {
+        return Ok((music_file.to_path_buf(), false));
+    }
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_nanos())
+        .unwrap_or_default();
+    let preview = std::env::temp_dir().join(format!(
+        "azulbox-preview-{}-{nanos}.ogg",
+        std::process::id()
+    ));
+    let output = Command::new(ffmpeg.unwrap_or("ffmpeg".into()))
+        .arg("-y")
+        .arg("-i")
+        .arg(music_file)
+        .arg("-vn")
+        .arg("-c:a")
+        .arg("libvorbis")
+        .arg("-q:a")
+        .arg("4")
+        .arg(&preview)
+        .output()?;
+    if output.status.success() {
+        Ok((preview, true))
+    } else {
+        let _ = fs::remove_file(&preview);
+        log::error!("{}", String::from_utf8_lossy(&output.stderr));
+        Err("Fail to prepare the audio for playback".into())
+    }
+}
+
+/// Plays one track and tells where it is, for the lyrics editor
+pub struct AudioPlayer {
+    _sink: MixerDeviceSink,
+    player: Player,
+    file: PathBuf,
+    temporary: bool,
+}
+
+impl AudioPlayer {
+    /// Starts paused
+    pub fn open(file: PathBuf, temporary: bool) -> Result<Self, Box<dyn Error>> {
+        let mut sink = DeviceSinkBuilder::open_default_sink()?;
+        sink.log_on_drop(false);
+        let player = Player::connect_new(sink.mixer());
+        player.pause();
+        let audio = Self {
+            _sink: sink,
+            player,
+            file,
+            temporary,
+        };
+        audio.load()?;
+        Ok(audio)
+    }
+
+    fn load(&self) -> Result<(), Box<dyn Error>> {
+        self.player
+            .append(Decoder::try_from(fs::File::open(&self.file)?)?);
+        Ok(())
+    }
+
+    /// Once the track ended it starts again from the top
+    pub fn play(&self) -> Result<(), Box<dyn Error>> {
+        if self.player.empty() {
+            self.load()?;
+        }
+        self.player.play();
+        Ok(())
+    }
+
+    pub fn pause(&self) {
+        self.player.pause();
+    }
+
+    pub fn is_playing(&self) -> bool {
+        !self.player.is_paused() && !self.player.empty()
+    }
+
+    /// Milliseconds from the start of the track
+    pub fn position(&self) -> u64 {
+        self.player.get_pos().as_millis() as u64
+    }
+
+    pub fn seek(&self, ms: u64) {
+        if let Err(e) = self.player.try_seek(Duration::from_millis(ms)) {
+            log::error!("Fail to seek: {e}");
+        }
+    }
+}
+
+impl Drop for AudioPlayer {
+    fn drop(&mut self) {
+        self.player.stop();
+        if self.temporary {
+            let _ = fs::remove_file(&self.file);
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use std::error::Error;
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Command, Output, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+pub type SharedProcess = Arc<ProcessHandle>;
+
+/// Owns the child process of one job so it can be cancelled without touching
+/// any other yt-dlp or ffmpeg running on the machine
+#[derive(Debug, Default)]
+pub struct ProcessHandle {
+    pid: Mutex<Option<u32>>,
+    cancelled: AtomicBool,
+}
+
+impl ProcessHandle {
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn check(&self) -> Result<(), Box<dyn Error>> {
+        if self.is_cancelled() {
+            Err("Cancelled".into())
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+        if let Some(pid) = *self.pid.lock().unwrap() {
+            log::info!("Cancel process tree {pid}");
+            kill_tree(pid);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn kill_tree(pid: u32) {
+    // The child leads its own process group, a negative pid reach ffmpeg spawned by yt-dlp too
+    if let Err(e) = Command::new("kill")
+        .arg("-TERM")
+        .arg("--")
+        .arg(format!("-{pid}"))
+        .output()
+    {
+        log::error!("Fail to kill {pid}: {e}");
+    }
+}
+
+#[cfg(windows)]
+fn kill_tree(pid: u32) {
+    if let Err(e) = Command::new("taskkill")
+        .args(["/PID", &pid.to_string(), "/T", "/F"])
+        .output()
+    {
+        log::error!("Fail to kill {pid}: {e}");
+    }
+}
+
+/// Like `Command::output` but hands every stdout line to `on_line` while the process runs
+pub fn run_streaming<F>(
+    cmd: &mut Command,
+    process: &ProcessHandle,
+    mut on_line: F,
+) -> Result<Output, Box<dyn Error>>
+where
+    F: FnMut(&str),
+{
+    process.check()?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        cmd.process_group(0);
+    }
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    *process.pid.lock().unwrap() = Some(child.id());
+    // Cancel may have been pressed between the check and the spawn
+    if process.is_cancelled() {
+        kill_tree(child.id());
+    }
+
+    // stderr is drained on its own thread so a chatty process never blocks on a full pipe
+    let mut stderr_pipe = child.stderr.take().ok_or("Fail to take stderr")?;
+    let stderr_reader = thread::spawn(move || {
+        let mut buf = Vec::new();
+        let _ = stderr_pipe.read_to_end(&mut buf);
+        buf
+    });
+
+    let mut stdout = Vec::new();
+    let stdout_pipe = child.stdout.take().ok_or("Fail to take stdout")?;
+    for line in BufReader::new(stdout_pipe).split(b'\n') {
+        let line = line?;
+        let text = String::from_utf8_lossy(&line);
+        on_line(text.trim_end_matches('\r'));
+        stdout.extend_from_slice(&line);
+        stdout.push(b'\n');
+    }
+
+    let status = child.wait()?;
+    *process.pid.lock().unwrap() = None;
+    let stderr = stderr_reader.join().unwrap_or_default();
+    process.check()?;
+    Ok(Output {
+        status,
+        stdout,
+        stderr,
+    })
+}
@@ -0,0 +1,179 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// Passed to yt-dlp with `--progress-template`, every field is raw so the parse stay simple.
+/// The title is last because it can contain the separator
+pub const TEMPLATE: &str = "download:azul-progress|%(progress.downloaded_bytes)s|%(progress.total_bytes)s|%(progress.total_bytes_estimate)s|%(progress.speed)s|%(progress.eta)s|%(info.playlist_index)s|%(info.n_entries)s|%(info.title)s";
+
+pub type SharedProgress = Arc<Mutex<Progress>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Phase {
+    #[default]
+    Waiting,
+    Download,
+    Merge,
+    Extract,
+    Thumbnail,
+    Subtitles,
+    Lyrics,
+    Tagging,
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Phase::Waiting => "Waiting",
+            Phase::Download => "Downloading",
+            Phase::Merge => "Merging",
+            Phase::Extract => "Extracting audio",
+            Phase::Thumbnail => "Thumbnail",
+            Phase::Subtitles => "Subtitles",
+            Phase::Lyrics => "Lyrics",
+            Phase::Tagging => "Tagging",
+        };
+        write!(f, "{name}")
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Progress {
+    pub phase: Phase,
+    /// 0.0 to 1.0, None when yt-dlp does not know the size
+    pub fraction: Option<f32>,
+    /// Bytes per second
+    pub speed: Option<f64>,
+    /// Seconds
+    pub eta: Option<u64>,
+    pub playlist_index: Option<u32>,
+    pub playlist_count: Option<u32>,
+    pub title: Option<String>,
+    /// Tracks MusicBrainz matched and tracks looked up, kept after the job is done
+    pub matched: Option<(u32, u32)>,
+}
+
+impl Progress {
+    pub fn set_phase(progress: &SharedProgress, phase: Phase) {
+        if let Ok(mut progress) = progress.lock() {
+            progress.phase = phase;
+            progress.fraction = None;
+            progress.speed = None;
+            progress.eta = None;
+        }
+    }
+
+    /// Feed one line of yt-dlp stdout
+    pub fn update(&mut self, line: &str) {
+        if let Some(fields) = line.strip_prefix("azul-progress|") {
+            let mut fields = fields.splitn(8, '|');
+            let downloaded = number(fields.next());
+            let total = number(fields.next());
+            let estimate = number(fields.next());
+            self.phase = Phase::Download;
+            self.fraction = match (downloaded, total.or(estimate)) {
+                (Some(downloaded), Some(total)) if total > 0.0 => {
+                    Some((downloaded / total).clamp(0.0, 1.0) as f32)
+                }
+                _ => None,
+            };
+            self.speed = number(fields.next());
+            self.eta = number(fields.next()).map(|eta| eta as u64);
+            if let Some(index) = number(fields.next()) {
+                self.playlist_index = Some(index as u32);
+            }
+            if let Some(count) = number(fields.next()) {
+                self.playlist_count = Some(count as u32);
+            }
+            if let Some(title) = fields.next()
+                && title != "NA"
+            {
+                self.title = Some(title.to_string());
+            }
+        } else if let Some(item) = line.strip_prefix("[download] Downloading item ") {
+            // "[download] Downloading item 3 of 10"
+            let mut parts = item.split(" of ");
+            self.playlist_index = parts.next().and_then(|i| i.trim().parse().ok());
+            self.playlist_count = parts.next().and_then(|i| i.trim().parse().ok());
+        } else {
+            let phase = if line.starts_with("[Merger]") {
+                Phase::Merge
+            } else if line.starts_with("[ExtractAudio]") {
+                Phase::Extract
+            } else if line.starts_with("[ThumbnailsConvertor]")
+                || line.starts_with("[EmbedThumbnail]")
+                || line.contains("Writing video thumbnail")
+            {
+                Phase::Thumbnail
+            } else if line.starts_with("[SubtitlesConvertor]")
+                || line.starts_with("[EmbedSubtitle]")
+                || line.contains("Writing video subtitles")
+            {
+                Phase::Subtitles
+            } else if line.starts_with("[Metadata]") {
+                Phase::Tagging
+            } else {
+                return;
+            };
+            self.phase = phase;
+            self.fraction = None;
+            self.speed = None;
+            self.eta = None;
+        }
+    }
+
+    pub fn speed_text(&self) -> Option<String> {
+        let speed = self.speed?;
+        if speed >= 1024.0 * 1024.0 {
+            Some(format!("{:.1} MiB/s", speed / 1024.0 / 1024.0))
+        } else {
+            Some(format!("{:.0} KiB/s", speed / 1024.0))
+        }
+    }
+
+    pub fn eta_text(&self) -> Option<String> {
+        let eta = self.eta?;
+        Some(format!("{:02}:{:02}", eta / 60, eta % 60))
+    }
+
+    pub fn matched_text(&self) -> Option<String> {
+        let (matched, tried) = self.matched?;
+        Some(format!("MusicBrainz matched {matched} of {tried}"))
+    }
+
+    pub fn playlist_text(&self) -> Option<String> {
+        match (self.playlist_index, self.playlist_count) {
+            (Some(index), Some(count)) => Some(format!("{index}/{count}")),
+            (Some(index), None) => Some(format!("{index}")),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}]", self.phase)?;
+        if let Some(item) = self.playlist_text() {
+            write!(f, " ({item})")?;
+        }
+        if let Some(fraction) = self.fraction {
+            write!(f, " {:.1}%", fraction * 100.0)?;
+        }
+        if let Some(speed) = self.speed_text() {
+            write!(f, " {speed}")?;
+        }
+        if let Some(eta) = self.eta_text() {
+            write!(f, " ETA {eta}")?;
+        }
+        if let Some((matched, tried)) = self.matched {
+            write!(f, " MB {matched}/{tried}")?;
+        }
+        if let Some(title) = &self.title {
+            write!(f, " {title}")?;
+        }
+        Ok(())
+    }
+}
+
+fn number(field: Option<&str>) -> Option<f64> {
+    field.and_then(|field| field.trim().parse::<f64>().ok())
+}
@@ -0,0 +1,293 @@
+use crate::app::cores::config::Config;
+use crate::app::cores::library::LibraryLyrics;
+use crate::app::cores::musicbrainz::SharedReview;
+use crate::app::cores::notify::{done_sound, fail_sound};
+use crate::app::cores::process::{ProcessHandle, SharedProcess};
+use crate::app::cores::progress::{Progress, SharedProgress};
+use crate::app::cores::translate::Translator;
+use crate::app::cores::ytdlp;
+use crate::app::ffmpeg::Convert;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum JobState {
+    Queued,
+    Running,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum JobKind {
+    Music(ytdlp::Music),
+    Video(ytdlp::Video),
+    Convert(Convert),
+    Library(LibraryLyrics),
+}
+
+impl JobKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            JobKind::Music(_) => "Music",
+            JobKind::Video(_) => "Video",
+            JobKind::Convert(_) => "FFmpeg",
+            JobKind::Library(_) => "Lyrics",
+        }
+    }
+    pub fn target(&self) -> &str {
+        match self {
+            JobKind::Music(music) => &music.link,
+            JobKind::Video(video) => &video.link,
+            JobKind::Convert(convert) => &convert.input,
+            JobKind::Library(library) => &library.directory,
+        }
+    }
+    /// Put back the api keys queue.json does not keep
+    fn restore_keys(&mut self, configs: &Config) {
+        if let JobKind::Music(music) = self {
+            music.acoustid_key = configs.music_dl.acoustid_key.clone().unwrap_or_default();
+        }
+        let translator = match self {
+            JobKind::Music(music) => &mut music.translator,
+            JobKind::Video(video) => &mut video.translator,
+            JobKind::Library(library) => &mut library.translator,
+            JobKind::Convert(_) => return,
+        };
+        translator.libretranslate_key = Translator::key_from_config(configs);
+    }
+    /// The MusicBrainz question a music job is waiting on
+    pub fn pending_review(&self) -> Option<&SharedReview> {
+        match self {
+            JobKind::Music(music)
+                if music.review && music.review_state.lock().unwrap().file.is_some() =>
+            {
+                Some(&music.review_state)
+            }
+            _ => None,
+        }
+    }
+    fn task(&self) -> &'static str {
+        match self {
+            JobKind::Music(_) => "Music Download",
+            JobKind::Video(_) => "Video Download",
+            JobKind::Convert(_) => "ffmpeg convertor",
+            JobKind::Library(_) => "Library lyrics",
+        }
+    }
+    fn run(
+        self,
+        progress: &SharedProgress,
+        process: &ProcessHandle,
+    ) -> Result<String, Box<dyn Error>> {
+        match self {
+            JobKind::Music(music) => music.download(progress, process),
+            JobKind::Video(video) => video.download(progress, process),
+            JobKind::Convert(convert) => convert.run(process),
+            JobKind::Library(library) => library.run(progress, process),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub id: u64,
+    pub kind: JobKind,
+    pub state: JobState,
+    pub error: Option<String>,
+    pub output: Option<String>,
+    #[serde(skip)]
+    pub progress: SharedProgress,
+    #[serde(skip)]
+    process: SharedProcess,
+}
+
+/// Jobs shared between the panels, the queue window and the workers.
+/// Every state change is written to `queue.json` so the queue survives restarts
+#[derive(Clone)]
+pub struct JobQueue {
+    jobs: Arc<Mutex<Vec<Job>>>,
+    concurrency: Arc<AtomicUsize>,
+    path: PathBuf,
+}
+
+impl JobQueue {
+    pub fn load(path: PathBuf, configs: &Config) -> Self {
+        let mut jobs: Vec<Job> = match fs::read_to_string(&path) {
+            Ok(data) => match serde_json::from_str(&data) {
+                Ok(jobs) => jobs,
+                Err(e) => {
+                    log::error!("Fail to read queue {e}");
+                    vec![]
+                }
+            },
+            Err(_) => vec![],
+        };
+        for job in jobs.iter_mut() {
+            // Jobs that were running when the app closed never finished
+            if job.state == JobState::Running {
+                job.state = JobState::Queued;
+            }
+            job.kind.restore_keys(configs);
+        }
+        let concurrency = configs.universal.concurrency.unwrap_or(2) as usize;
+        Self {
+            jobs: Arc::new(Mutex::new(jobs)),
+            concurrency: Arc::new(AtomicUsize::new(concurrency.max(1))),
+            path,
+        }
+    }
+
+    pub fn push(&self, kind: JobKind) -> u64 {
+        let mut jobs = self.jobs.lock().unwrap();
+        let id = jobs.iter().map(|job| job.id).max().unwrap_or(0) + 1;
+        log::info!("Queued job {id}: {} {}", kind.name(), kind.target());
+        jobs.push(Job {
+            id,
+            kind,
+            state: JobState::Queued,
+            error: None,
+            output: None,
+            progress: SharedProgress::default(),
+            process: SharedProcess::default(),
+        });
+        self.save(&jobs);
+        id
+    }
+
+    pub fn get(&self, id: u64) -> Option<Job> {
+        let jobs = self.jobs.lock().unwrap();
+        jobs.iter().find(|job| job.id == id).cloned()
+    }
+
+    pub fn jobs(&self) -> Vec<Job> {
+        self.jobs.lock().unwrap().clone()
+    }
+
+    pub fn is_busy(&self) -> bool {
+        let jobs = self.jobs.lock().unwrap();
+        jobs.iter()
+            .any(|job| matches!(job.state, JobState::Queued | JobState::Running))
+    }
+
+    pub fn remove(&self, id: u64) {
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.retain(|job| job.id != id || job.state == JobState::Running);
+        self.save(&jobs);
+    }
+
+    pub fn retry(&self, id: u64) {
+        let mut jobs = self.jobs.lock().unwrap();
+        if let Some(job) = jobs
+            .iter_mut()
+            .find(|job| job.id == id && matches!(job.state, JobState::Failed | JobState::Cancelled))
+        {
+            job.state = JobState::Queued;
+            job.error = None;
+        }
+        self.save(&jobs);
+    }
+
+    /// Kill the process of a running job, or drop a job that has not started yet
+    pub fn cancel(&self, id: u64) {
+        let mut jobs = self.jobs.lock().unwrap();
+        if let Some(job) = jobs.iter_mut().find(|job| job.id == id) {
+            match job.state {
+                JobState::Running => job.process.cancel(),
+                JobState::Queued => job.state = JobState::Cancelled,
+                _ => {}
+            }
+        }
+        self.save(&jobs);
+    }
+
+    pub fn clear_finished(&self) {
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.retain(|job| matches!(job.state, JobState::Queued | JobState::Running));
+        self.save(&jobs);
+    }
+
+    pub fn concurrency(&self) -> usize {
+        self.concurrency.load(Ordering::Relaxed)
+    }
+
+    pub fn set_concurrency(&self, concurrency: usize) {
+        self.concurrency
+            .store(concurrency.max(1), Ordering::Relaxed);
+    }
+
+    /// Start queued jobs until the concurrency limit is reached
+    pub fn tick(&self) {
+        let mut jobs = self.jobs.lock().unwrap();
+        let mut running = jobs
+            .iter()
+            .filter(|job| job.state == JobState::Running)
+            .count();
+        let mut started = false;
+        for job in jobs.iter_mut() {
+            if running >= self.concurrency() {
+                break;
+            }
+            if job.state != JobState::Queued {
+                continue;
+            }
+            job.state = JobState::Running;
+            running += 1;
+            started = true;
+
+            let id = job.id;
+            let kind = job.kind.clone();
+            let progress = job.progress.clone();
+            *progress.lock().unwrap() = Progress::default();
+            job.process = SharedProcess::default();
+            let process = job.process.clone();
+            let queue = self.clone();
+            // Jobs block on downloads and child processes, keep them off the async workers
+            tokio::task::spawn_blocking(move || {
+                let task = kind.task();
+                match kind.run(&progress, &process) {
+                    Ok(output) => {
+                        queue.finish(id, JobState::Done, Some(output.clone()), None);
+                        let _ = done_sound(task, output);
+                    }
+                    Err(_) if process.is_cancelled() => {
+                        queue.finish(id, JobState::Cancelled, None, None);
+                    }
+                    Err(e) => {
+                        queue.finish(id, JobState::Failed, None, Some(e.to_string()));
+                        let _ = fail_sound(task);
+                    }
+                }
+            });
+        }
+        if started {
+            self.save(&jobs);
+        }
+    }
+
+    fn finish(&self, id: u64, state: JobState, output: Option<String>, error: Option<String>) {
+        let mut jobs = self.jobs.lock().unwrap();
+        if let Some(job) = jobs.iter_mut().find(|job| job.id == id) {
+            job.state = state;
+            job.output = output;
+            job.error = error;
+        }
+        self.save(&jobs);
+    }
+
+    fn save(&self, jobs: &[Job]) {
+        match serde_json::to_string(jobs) {
+            Ok(data) => {
+                if let Err(e) = fs::write(&self.path, data) {
+                    log::error!("Fail to save queue {e}");
+                }
+            }
+            Err(e) => log::error!("Fail to serialize queue {e}"),
+        }
+    }
+}
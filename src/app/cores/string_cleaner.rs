@@ -11,3 +11,14 @@ pub fn clean_title_before_api_call(title: &str, author_name: &str) -> String {
         .trim()
         .to_string()
 }
+
+/// Channel names used as artist: `Artist - Topic` from YouTube Music, `ArtistVEVO`
+pub fn clean_artist(artist: &str) -> String {
+    let artist = artist.trim();
+    let artist = artist.strip_suffix(" - Topic").unwrap_or(artist);
+    let artist = match artist.strip_suffix("VEVO") {
+        Some(name) if !name.is_empty() => name,
+        _ => artist,
+    };
+    artist.trim().to_string()
+}
@@ -0,0 +1,460 @@
+use crate::app::cores::files::change_ext;
+use crate::app::cores::lrc::{Lrc, LrcLine, parse_time};
+use crate::app::cores::lyrics;
+use lofty::prelude::*;
+use lofty::probe::Probe;
+use std::error::Error;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Extensions the subtitle converter reads and writes
+pub const SUBTITLE_FORMAT: &[&str] = &["srt", "vtt", "ass", "ssa", "lrc"];
+
+/// LRC only says when a line starts, it ends with the next one but never shows longer than this
+const MAX_LINE: u64 = 10_000;
+/// A pause longer than this between two cues gets an empty LRC line so the lyrics clear
+const LRC_GAP: u64 = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubtitleFormat {
+    Srt,
+    Vtt,
+    Ass,
+    Lrc,
+}
+
+impl SubtitleFormat {
+    pub fn from_ext(ext: &str) -> Option<Self> {
+        match ext.to_lowercase().as_str() {
+            "srt" => Some(SubtitleFormat::Srt),
+            "vtt" => Some(SubtitleFormat::Vtt),
+            "ass" | "ssa" => Some(SubtitleFormat::Ass),
+            "lrc" => Some(SubtitleFormat::Lrc),
+            _ => None,
+        }
+    }
+}
+
+/// One subtitle on screen, times in milliseconds
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cue {
+    pub start: u64,
+    pub end: u64,
+    /// Lines are separated by `\n`
+    pub text: String,
+}
+
+pub fn parse(text: &str, format: SubtitleFormat) -> Vec<Cue> {
+    match format {
+        SubtitleFormat::Srt | SubtitleFormat::Vtt => parse_blocks(text),
+        SubtitleFormat::Ass => parse_ass(text),
+        SubtitleFormat::Lrc => {
+            // Cues have no offset of their own
+            let mut lrc = Lrc::parse(text);
+            lrc.apply_offset();
+            from_lrc(&lrc, None)
+        }
+    }
+}
+
+pub fn write(cues: &[Cue], format: SubtitleFormat) -> String {
+    match format {
+        SubtitleFormat::Srt => write_srt(cues),
+        SubtitleFormat::Vtt => write_vtt(cues),
+        SubtitleFormat::Ass => write_ass(cues),
+        SubtitleFormat::Lrc => to_lrc(cues).to_string(),
+    }
+}
+
+/// Convert `input` into `directory` as `<name>-azul.<format_out>`
+pub fn convert_file(
+    input: &Path,
+    directory: &Path,
+    format_out: &str,
+) -> Result<PathBuf, Box<dyn Error>> {
+    let ext = input
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .ok_or("No extension on the input")?;
+    let from = SubtitleFormat::from_ext(ext).ok_or("Input is not a subtitle")?;
+    let to = SubtitleFormat::from_ext(format_out).ok_or("Output is not a subtitle")?;
+    let stem = input
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .ok_or("Failed to extract filename stem")?;
+    let output = directory.join(format!("{stem}-azul.{format_out}"));
+    let cues = parse(&fs::read_to_string(input)?, from);
+    if cues.is_empty() {
+        return Err("No subtitle found in the input".into());
+    }
+    fs::write(&output, write(&cues, to))?;
+    log::info!("Converted {} cues to {output:?}", cues.len());
+    Ok(output)
+}
+
+/// Write the synced lyrics of `music_file` next to it as `.srt`, for making lyric videos
+pub fn lyrics_srt(music_file: &Path) -> Result<PathBuf, Box<dyn Error>> {
+    let lyrics = match lyrics::read(music_file)? {
+        Some(lyrics) => lyrics,
+        // Lyrics the tag could not take are in a `.lrc` file
+        None => fs::read_to_string(change_ext(music_file, "lrc")).map_err(|_| "No lyrics found")?,
+    };
+    let mut lrc = Lrc::parse(&lyrics);
+    lrc.apply_offset();
+    if !lrc.is_synced() {
+        return Err("Lyrics are not synced".into());
+    }
+    let length = Probe::open(music_file)?
+        .read()?
+        .properties()
+        .duration()
+        .as_millis() as u64;
+    let srt = change_ext(music_file, "srt");
+    fs::write(
+        &srt,
+        write_srt(&from_lrc(&lrc, (length > 0).then_some(length))),
+    )?;
+    Ok(srt)
+}
+
+/// Every line lasts until the next one. Lines sharing a time, like an interleaved
+/// translation, become one cue and empty lines only end the one before them.
+/// `length` is when the track ends, it bounds the last line
+pub fn from_lrc(lrc: &Lrc, length: Option<u64>) -> Vec<Cue> {
+    let mut cues: Vec<Cue> = vec![];
+    for (index, line) in lrc.lines.iter().enumerate() {
+        if line.text.is_empty() {
+            continue;
+        }
+        if let Some(last) = cues.last_mut()
+            && last.start == line.time
+        {
+            last.text = format!("{}\n{}", last.text, line.text);
+            continue;
+        }
+        let next = lrc.lines[index + 1..]
+            .iter()
+            .map(|next| next.time)
+            .find(|time| *time > line.time);
+        let limit = line.time.saturating_add(MAX_LINE);
+        let end = match (next, length) {
+            (Some(next), _) => next.min(limit),
+            (None, Some(length)) if length > line.time => length.min(limit),
+            _ => limit,
+        };
+        cues.push(Cue {
+            start: line.time,
+            end,
+            text: line.text.clone(),
+        });
+    }
+    cues
+}
+
+/// LRC has no end time, an empty line clears the screen when the next cue is not right after
+pub fn to_lrc(cues: &[Cue]) -> Lrc {
+    let mut lrc = Lrc::default();
+    for (index, cue) in cues.iter().enumerate() {
+        lrc.lines
+            .push(LrcLine::new(cue.start, &cue.text.replace('\n', " ")));
+        let next = cues.get(index + 1).map(|next| next.start);
+        if next.is_none_or(|next| next > cue.end.saturating_add(LRC_GAP)) {
+            lrc.lines.push(LrcLine::new(cue.end, ""));
+        }
+    }
+    lrc
+}
+
+/// SRT and WebVTT are both blank line separated blocks with a `start --> end` line
+fn parse_blocks(text: &str) -> Vec<Cue> {
+    let mut cues = vec![];
+    let text = text.replace("\r\n", "\n");
+    for block in text.split("\n\n") {
+        let mut lines = block.lines().skip_while(|line| !line.contains("-->"));
+        let Some(timing) = lines.next() else {
+            // Numbers, WEBVTT header, NOTE and STYLE blocks
+            continue;
+        };
+        let Some((start, end)) = timing.split_once("-->") else {
+            continue;
+        };
+        // Cue settings like `align:start` follow the end time
+        let end = end.split_whitespace().next().unwrap_or_default();
+        let (Some(start), Some(end)) = (cue_time(start), cue_time(end)) else {
+            continue;
+        };
+        let text = lines
+            .map(strip_tags)
+            .filter(|line| !line.trim().is_empty())
+            .collect::<Vec<String>>()
+            .join("\n");
+        if !text.is_empty() {
+            cues.push(Cue { start, end, text });
+        }
+    }
+    cues
+}
+
+fn cue_time(time: &str) -> Option<u64> {
+    parse_time(&time.trim().replace(',', "."))
+}
+
+/// Drop `<i>`, `<c.color>` and the `<00:00:01.000>` karaoke times of WebVTT
+fn strip_tags(line: &str) -> String {
+    let mut text = String::new();
+    let mut rest = line;
+    while let Some(start) = rest.find('<') {
+        let Some(end) = rest[start..].find('>') else {
+            break;
+        };
+        text.push_str(&rest[..start]);
+        rest = &rest[start + end + 1..];
+    }
+    text.push_str(rest);
+    text.replace("&amp;", "&")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", " ")
+}
+
+fn parse_ass(text: &str) -> Vec<Cue> {
+    let mut cues = vec![];
+    let mut format: Vec<String> = vec![];
+    let mut in_events = false;
+    for line in text.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_events = line.eq_ignore_ascii_case("[events]");
+            continue;
+        }
+        if !in_events {
+            continue;
+        }
+        if let Some(fields) = line.strip_prefix("Format:") {
+            format = fields.split(',').map(|f| f.trim().to_lowercase()).collect();
+            continue;
+        }
+        let Some(fields) = line.strip_prefix("Dialogue:") else {
+            continue;
+        };
+        if format.is_empty() {
+            continue;
+        }
+        // Text is last and may contain commas
+        let values: Vec<&str> = fields.splitn(format.len(), ',').collect();
+        let field = |name: &str| {
+            format
+                .iter()
+                .position(|f| f == name)
+                .and_then(|index| values.get(index))
+                .map(|value| value.trim())
+        };
+        let (Some(start), Some(end), Some(text)) = (
+            field("start").and_then(parse_time),
+            field("end").and_then(parse_time),
+            field("text"),
+        ) else {
+            continue;
+        };
+        let text = strip_overrides(text);
+        if !text.is_empty() {
+            cues.push(Cue { start, end, text });
+        }
+    }
+    cues.sort_by_key(|cue| cue.start);
+    cues
+}
+
+/// Drop `{\b1}` style overrides and turn the ASS line breaks into real ones
+fn strip_overrides(text: &str) -> String {
+    let mut plain = String::new();
+    let mut depth = 0;
+    for c in text.chars() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth > 0 => depth -= 1,
+            _ if depth == 0 => plain.push(c),
+            _ => {}
+        }
+    }
+    plain
+        .replace("\\N", "\n")
+        .replace("\\n", "\n")
+        .replace("\\h", " ")
+        .trim()
+        .to_string()
+}
+
+/// `hh:mm:ss<separator>mmm`
+fn clock(ms: u64, separator: char) -> String {
+    format!(
+        "{:02}:{:02}:{:02}{separator}{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000
+    )
+}
+
+fn write_srt(cues: &[Cue]) -> String {
+    let mut srt = String::new();
+    for (index, cue) in cues.iter().enumerate() {
+        let _ = write!(
+            srt,
+            "{}\n{} --> {}\n{}\n\n",
+            index + 1,
+            clock(cue.start, ','),
+            clock(cue.end, ','),
+            cue.text
+        );
+    }
+    srt
+}
+
+fn write_vtt(cues: &[Cue]) -> String {
+    let mut vtt = String::from("WEBVTT\n\n");
+    for cue in cues {
+        let _ = write!(
+            vtt,
+            "{} --> {}\n{}\n\n",
+            clock(cue.start, '.'),
+            clock(cue.end, '.'),
+            cue.text
+        );
+    }
+    vtt
+}
+
+fn write_ass(cues: &[Cue]) -> String {
+    // `h:mm:ss.cc`
+    let time = |ms: u64| {
+        let centis = ms / 10;
+        format!(
+            "{}:{:02}:{:02}.{:02}",
+            centis / 360_000,
+            centis / 6000 % 60,
+            centis / 100 % 60,
+            centis % 100
+        )
+    };
+    let mut ass = String::from(
+        "[Script Info]\nScriptType: v4.00+\nPlayResX: 1920\nPlayResY: 1080\n\n\
+         [V4+ Styles]\n\
+         Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\n\
+         Style: Default,Arial,64,&H00FFFFFF,&H000000FF,&H00000000,&H80000000,0,0,0,0,100,100,0,0,1,3,0,2,60,60,60,1\n\n\
+         [Events]\n\
+         Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n",
+    );
+    for cue in cues {
+        let _ = writeln!(
+            ass,
+            "Dialogue: 0,{},{},Default,,0,0,0,,{}",
+            time(cue.start),
+            time(cue.end),
+            cue.text.replace('\n', "\\N")
+        );
+    }
+    ass
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cue(start: u64, end: u64, text: &str) -> Cue {
+        Cue {
+            start,
+            end,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn parse_srt() {
+        let srt = "1\r\n00:00:01,000 --> 00:00:02,500\r\n<i>Hello</i>\r\nthere\r\n\r\n\
+                   2\r\n00:00:03,000 --> 00:00:04,000\r\nBye\r\n";
+        assert_eq!(
+            parse(srt, SubtitleFormat::Srt),
+            [cue(1000, 2500, "Hello\nthere"), cue(3000, 4000, "Bye")]
+        );
+    }
+
+    #[test]
+    fn parse_vtt() {
+        let vtt = "WEBVTT\n\nNOTE a comment\n\n\
+                   00:01.000 --> 00:02.000 align:start\n<c.red>Tom &amp; Jerry</c>\n\n\
+                   id\n00:00:03.000 --> 00:00:04.000\n<00:00:03.500>Karaoke\n";
+        assert_eq!(
+            parse(vtt, SubtitleFormat::Vtt),
+            [cue(1000, 2000, "Tom & Jerry"), cue(3000, 4000, "Karaoke")]
+        );
+    }
+
+    #[test]
+    fn parse_ass_events() {
+        let ass = "[Script Info]\nTitle: x\n\n[Events]\n\
+                   Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n\
+                   Dialogue: 0,0:00:03.00,0:00:04.00,Default,,0,0,0,,Second, with a comma\n\
+                   Dialogue: 0,0:00:01.00,0:00:02.50,Default,,0,0,0,,{\\b1}First\\Nline{\\b0}\n";
+        assert_eq!(
+            parse(ass, SubtitleFormat::Ass),
+            [
+                cue(1000, 2500, "First\nline"),
+                cue(3000, 4000, "Second, with a comma")
+            ]
+        );
+    }
+
+    #[test]
+    fn written_formats_read_back() {
+        // Whole centiseconds, what ASS can keep
+        let cues = [
+            cue(1000, 2500, "First\nline"),
+            cue(3_723_450, 3_725_000, "Last"),
+        ];
+        for format in [
+            SubtitleFormat::Srt,
+            SubtitleFormat::Vtt,
+            SubtitleFormat::Ass,
+        ] {
+            assert_eq!(parse(&write(&cues, format), format), cues, "{format:?}");
+        }
+    }
+
+    #[test]
+    fn lrc_lines_end_at_the_next_one() {
+        let lrc =
+            Lrc::parse("[00:01.00]One\n[00:01.00]Un\n[00:03.00]Two\n[00:04.00]\n[00:20.00]Three");
+        assert_eq!(
+            from_lrc(&lrc, Some(25_000)),
+            [
+                cue(1000, 3000, "One\nUn"),
+                cue(3000, 4000, "Two"),
+                cue(20_000, 25_000, "Three")
+            ]
+        );
+    }
+
+    #[test]
+    fn lrc_offset_applies_to_cues() {
+        let cues = parse(
+            "[offset:500]\n[00:02.00]Early\n[00:04.00]Late",
+            SubtitleFormat::Lrc,
+        );
+        assert_eq!(cues, [cue(1500, 3500, "Early"), cue(3500, 13_500, "Late")]);
+    }
+
+    #[test]
+    fn cues_to_lrc_clear_on_gaps() {
+        let cues = [
+            cue(1000, 2000, "One\ntwo"),
+            cue(2500, 3000, "Three"),
+            cue(9000, 9500, "Four"),
+        ];
+        assert_eq!(
+            write(&cues, SubtitleFormat::Lrc),
+            "[00:01.00]One two\n[00:02.50]Three\n[00:03.00]\n[00:09.00]Four\n[00:09.50]"
+        );
+    }
+}
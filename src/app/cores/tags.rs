@@ -0,0 +1,174 @@
+use lofty::picture::{MimeType, Picture, PictureType};
+use lofty::prelude::*;
+use lofty::probe::Probe;
+use lofty::tag::{ItemValue, TagItem};
+use std::error::Error;
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+
+use crate::app::cores::files::writable_tag;
+use crate::app::cores::{language, lyrics, musicbrainz};
+
+/// Shown for a field that differs between the files, left as is it keeps every value
+pub const KEEP: &str = "<keep>";
+/// Several genres are written as one item each
+const GENRE_SEPARATOR: &str = "; ";
+
+pub const FIELDS: [(ItemKey, &str); 13] = [
+    (ItemKey::TrackTitle, "Title"),
+    (ItemKey::TrackArtist, "Artist"),
+    (ItemKey::AlbumTitle, "Album"),
+    (ItemKey::AlbumArtist, "Album artist"),
+    (ItemKey::RecordingDate, "Date"),
+    (ItemKey::TrackNumber, "Track"),
+    (ItemKey::TrackTotal, "Track total"),
+    (ItemKey::DiscNumber, "Disc"),
+    (ItemKey::DiscTotal, "Disc total"),
+    (ItemKey::Genre, "Genre"),
+    (ItemKey::Composer, "Composer"),
+    (ItemKey::Label, "Label"),
+    (ItemKey::Comment, "Comment"),
+];
+
+/// What the editor shows of one file
+#[derive(Debug, Clone, Default)]
+pub struct TagInfo {
+    /// One per `FIELDS`, empty when the tag has none
+    pub values: Vec<String>,
+    pub pictures: Vec<Picture>,
+    pub lyrics: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum CoverEdit {
+    #[default]
+    Keep,
+    Replace(Vec<u8>),
+    Remove,
+}
+
+/// Changes for every file of the batch, `None` and `KEEP` leave a field alone
+#[derive(Debug, Clone, Default)]
+pub struct TagEdit {
+    pub values: Vec<Option<String>>,
+    pub cover: CoverEdit,
+    pub lyrics: Option<String>,
+}
+
+pub fn read(music_file: &Path) -> Result<TagInfo, Box<dyn Error>> {
+    let tagged_file = Probe::open(music_file)?.read()?;
+    let lyrics = lyrics::read(music_file)?.unwrap_or_default();
+    let Some(tag) = tagged_file.primary_tag().or(tagged_file.first_tag()) else {
+        return Ok(TagInfo {
+            values: vec![String::new(); FIELDS.len()],
+            pictures: vec![],
+            lyrics,
+        });
+    };
+    Ok(TagInfo {
+        values: FIELDS
+            .iter()
+            .map(|(key, _)| {
+                tag.get_strings(*key)
+                    .collect::<Vec<&str>>()
+                    .join(GENRE_SEPARATOR)
+            })
+            .collect(),
+        pictures: tag.pictures().to_vec(),
+        lyrics,
+    })
+}
+
+/// The values of every file, `KEEP` where they differ
+pub fn merge(infos: &[TagInfo]) -> (Vec<String>, String) {
+    let Some(first) = infos.first() else {
+        return (vec![String::new(); FIELDS.len()], String::new());
+    };
+    let values = (0..FIELDS.len())
+        .map(|index| {
+            if infos
+                .iter()
+                .all(|info| info.values[index] == first.values[index])
+            {
+                first.values[index].clone()
+            } else {
+                KEEP.to_string()
+            }
+        })
+        .collect();
+    let lyrics = if infos.iter().all(|info| info.lyrics == first.lyrics) {
+        first.lyrics.clone()
+    } else {
+        KEEP.to_string()
+    };
+    (values, lyrics)
+}
+
+/// Write `edit` into `music_file`, an empty value removes the field
+pub fn write(music_file: &Path, edit: &TagEdit) -> Result<(), Box<dyn Error>> {
+    // The generic tag has no place for timed lyrics, they are written again after it
+    let old_lyrics = lyrics::read(music_file)?.unwrap_or_default();
+    let mut tagged_file = Probe::open(music_file)?.read()?;
+    let tag = writable_tag(&mut tagged_file).ok_or("Fail to open tag")?;
+    for ((key, _), value) in FIELDS.iter().zip(&edit.values) {
+        let Some(value) = value.as_deref().filter(|value| *value != KEEP) else {
+            continue;
+        };
+        tag.remove_key(*key);
+        if *key == ItemKey::Genre {
+            for genre in value
+                .split(GENRE_SEPARATOR.trim())
+                .map(str::trim)
+                .filter(|genre| !genre.is_empty())
+            {
+                tag.push(TagItem::new(*key, ItemValue::Text(genre.to_string())));
+            }
+        } else if !value.trim().is_empty() {
+            tag.insert_text(*key, value.trim().to_string());
+        }
+    }
+    match &edit.cover {
+        CoverEdit::Keep => {}
+        CoverEdit::Replace(data) => {
+            let mut picture = Picture::from_reader(&mut Cursor::new(data))?;
+            picture.set_pic_type(PictureType::CoverFront);
+            tag.remove_picture_type(PictureType::CoverFront);
+            tag.push_picture(picture);
+        }
+        CoverEdit::Remove => tag.remove_picture_type(PictureType::CoverFront),
+    }
+    let lyrics = match edit.lyrics.as_deref().filter(|lyrics| *lyrics != KEEP) {
+        Some(lyrics) => lyrics.to_string(),
+        None => old_lyrics,
+    };
+    musicbrainz::save_tag(tag, music_file)?;
+    if lyrics.trim().is_empty() {
+        lyrics::clear(music_file)?;
+    } else {
+        let lang = language::detect(&lyrics);
+        lyrics::embed(music_file, &lyrics, lang.as_deref(), false)?;
+    }
+    Ok(())
+}
+
+/// Extension a picture is saved with
+pub fn picture_ext(picture: &Picture) -> &'static str {
+    match picture.mime_type() {
+        Some(MimeType::Png) => "png",
+        Some(MimeType::Gif) => "gif",
+        Some(MimeType::Bmp) => "bmp",
+        Some(MimeType::Tiff) => "tiff",
+        _ => match image::guess_format(picture.data()) {
+            Ok(image::ImageFormat::Png) => "png",
+            Ok(image::ImageFormat::WebP) => "webp",
+            _ => "jpg",
+        },
+    }
+}
+
+pub fn extract_picture(picture: &Picture, to: &Path) -> Result<(), Box<dyn Error>> {
+    fs::write(to, picture.data())?;
+    log::info!("Picture saved to {to:?}");
+    Ok(())
+}
@@ -1,29 +1,355 @@
+use crate::app::cores::config;
+use crate::app::cores::lrc::Lrc;
+use crate::app::cores::subtitle::Cue;
 use log::info;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use url::form_urlencoded;
+use std::error::Error;
+use std::fmt;
+use std::fs;
 
-pub fn translate(to: &str, text: &str) -> Result<String, Box<dyn std::error::Error>> {
-    let en_text: String = form_urlencoded::byte_serialize(text.as_bytes()).collect();
+/// Longest text sent in one request, chunks are cut between lines
+const CHUNK_SIZE: usize = 1800;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TranslationBackend {
+    /// The free `translate.googleapis.com` gtx endpoint
+    #[default]
+    Google,
+    /// Any LibreTranslate compatible server
+    LibreTranslate,
+    /// Never translate
+    None,
+}
+
+impl fmt::Display for TranslationBackend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            TranslationBackend::Google => "Google",
+            TranslationBackend::LibreTranslate => "LibreTranslate",
+            TranslationBackend::None => "None",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Taken from the config when a job is queued, workers never read the config file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Translator {
+    pub backend: TranslationBackend,
+    pub libretranslate_url: String,
+    /// Kept out of queue.json, a restored job reads it from the config again
+    #[serde(skip)]
+    pub libretranslate_key: Option<String>,
+}
+
+impl Translator {
+    /// The backend chosen in `configs`
+    pub fn from_config(configs: &config::Config) -> Self {
+        Self {
+            backend: configs.universal.translator.unwrap_or_default(),
+            libretranslate_url: configs
+                .universal
+                .libretranslate_url
+                .clone()
+                .unwrap_or_default(),
+            libretranslate_key: Self::key_from_config(configs),
+        }
+    }
+
+    pub fn key_from_config(configs: &config::Config) -> Option<String> {
+        configs
+            .universal
+            .libretranslate_key
+            .clone()
+            .filter(|key| !key.is_empty())
+    }
+
+    pub fn translate(&self, to: &str, text: &str) -> Result<String, Box<dyn Error>> {
+        if self.backend == TranslationBackend::None {
+            return Err("Translation is disabled".into());
+        }
+        let mut translated = vec![];
+        for chunk in chunks(text) {
+            translated.push(self.translate_chunk(to, &chunk)?);
+        }
+        info!("Translate success!");
+        Ok(translated.join("\n"))
+    }
+
+    fn translate_chunk(&self, to: &str, chunk: &str) -> Result<String, Box<dyn Error>> {
+        if chunk.trim().is_empty() {
+            return Ok(chunk.to_string());
+        }
+        let cache = config::get_translate_cache_dir().join(format!(
+            "{:016x}.txt",
+            fnv1a(&[
+                &self.backend.to_string(),
+                &self.libretranslate_url,
+                to,
+                chunk,
+            ])
+        ));
+        if let Ok(cached) = fs::read_to_string(&cache) {
+            info!("Translation from cache {cache:?}");
+            return Ok(cached);
+        }
+        let translated = match self.backend {
+            TranslationBackend::Google => google(to, chunk)?,
+            TranslationBackend::LibreTranslate => self.libretranslate(to, chunk)?,
+            TranslationBackend::None => return Err("Translation is disabled".into()),
+        };
+        if let Err(e) = fs::write(&cache, &translated) {
+            log::error!("Fail to cache translation {e}");
+        }
+        Ok(translated)
+    }
+
+    fn libretranslate(&self, to: &str, text: &str) -> Result<String, Box<dyn Error>> {
+        if self.libretranslate_url.is_empty() {
+            return Err("No LibreTranslate url set".into());
+        }
+        let url = format!(
+            "{}/translate",
+            self.libretranslate_url.trim_end_matches('/')
+        );
+        let mut body = serde_json::json!({
+            "q": text,
+            "source": "auto",
+            "target": to,
+            "format": "text",
+        });
+        if let Some(key) = &self.libretranslate_key {
+            body["api_key"] = Value::String(key.clone());
+        }
+        let response = ureq::post(&url)
+            .send_json(body)?
+            .body_mut()
+            .read_json::<LibreTranslateResponse>()?;
+        Ok(response.translated_text)
+    }
+}
+
+#[derive(Deserialize)]
+struct LibreTranslateResponse {
+    #[serde(rename = "translatedText")]
+    translated_text: String,
+}
+
+fn google(to: &str, text: &str) -> Result<String, Box<dyn Error>> {
+    // POST keeps long lyrics out of the url, GET gets truncated
     let url = format!(
-        "https://translate.googleapis.com/translate_a/single?client=gtx&sl=auto&tl={}&dt=t&q={}",
-        to, en_text
+        "https://translate.googleapis.com/translate_a/single?client=gtx&sl=auto&tl={}&dt=t",
+        to
     );
+    let json_as_string = ureq::post(&url)
+        .send_form([("q", text)])?
+        .body_mut()
+        .read_to_string()?;
+    let values = serde_json::from_str::<Value>(&json_as_string)?;
+    let list = values
+        .get(0)
+        .and_then(|value| value.as_array())
+        .ok_or("Unexpected translate response")?;
+    Ok(list
+        .iter()
+        .filter_map(|v| v.get(0).and_then(|v| v.as_str()))
+        .collect::<Vec<&str>>()
+        .join(""))
+}
 
-    let mut translated_text = text.to_string();
+/// Cut text into pieces under `CHUNK_SIZE`, only between lines
+fn chunks(text: &str) -> Vec<String> {
+    let mut chunks: Vec<Vec<&str>> = vec![vec![]];
+    let mut size = 0;
+    for line in text.split('\n') {
+        if let Some(current) = chunks.last()
+            && !current.is_empty()
+            && size + line.len() + 1 > CHUNK_SIZE
+        {
+            chunks.push(vec![]);
+            size = 0;
+        }
+        if let Some(current) = chunks.last_mut() {
+            current.push(line);
+        }
+        size += line.len() + 1;
+    }
+    chunks.into_iter().map(|chunk| chunk.join("\n")).collect()
+}
 
-    let json_as_string = ureq::get(&url).call()?.body_mut().read_to_string()?;
-    let values = serde_json::from_str::<Value>(&json_as_string)?;
-    if let Some(value) = values.get(0) {
-        info!("{:?}", value.as_str());
-        if let Some(list) = value.as_array() {
-            let lyrics: Vec<String> = list
-                .iter()
-                .filter_map(|v| v.get(0).and_then(|v| v.as_str()))
-                .map(|s| s.to_string())
-                .collect();
-            translated_text = lyrics.join("");
-            info!("Translate success!");
-        }
-    }
-    Ok(translated_text)
+/// FNV-1a, stable between runs unlike the std hasher
+fn fnv1a(parts: &[&str]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for part in parts {
+        for byte in part.bytes().chain([0]) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    hash
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TranslationMode {
+    /// Keep the lyrics as found
+    Off,
+    /// Embed only the translation
+    #[default]
+    Replace,
+    /// Embed each original line followed by its translation at the same time
+    Interleave,
+    /// Embed the original and write the translation to `<name>.<lang>.lrc`
+    Sidecar,
+}
+
+impl fmt::Display for TranslationMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            TranslationMode::Off => "Off",
+            TranslationMode::Replace => "Replace",
+            TranslationMode::Interleave => "Interleave",
+            TranslationMode::Sidecar => "Sidecar",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Translate every line on its own so none get merged or split
+fn translate_lines(
+    translator: &Translator,
+    to: &str,
+    lines: &[&str],
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let translated = translator.translate(to, &lines.join("\n"))?;
+    let translated: Vec<String> = translated
+        .split('\n')
+        .map(|line| line.trim().to_string())
+        .collect();
+    if translated.len() != lines.len() {
+        return Err(format!(
+            "Translation returned {} lines for {}",
+            translated.len(),
+            lines.len()
+        )
+        .into());
+    }
+    Ok(translated)
+}
+
+/// Translate lyrics keeping their timestamps and line breaks
+pub fn translate_lyrics(
+    translator: &Translator,
+    to: &str,
+    lyrics: &str,
+) -> Result<String, Box<dyn Error>> {
+    let mut lrc = Lrc::parse(lyrics);
+    if !lrc.is_synced() {
+        let lines: Vec<&str> = lyrics.lines().collect();
+        return Ok(fill_lines(translator, to, &lines)?.join("\n"));
+    }
+    let lines: Vec<&str> = lrc.lines.iter().map(|line| line.text.as_str()).collect();
+    let translated = fill_lines(translator, to, &lines)?;
+    for (line, text) in lrc.lines.iter_mut().zip(translated) {
+        line.text = text;
+        line.words.clear();
+    }
+    Ok(lrc.to_string())
+}
+
+/// Translate subtitles keeping the timing and the line breaks of every cue
+pub fn translate_cues(
+    translator: &Translator,
+    to: &str,
+    cues: &[Cue],
+) -> Result<Vec<Cue>, Box<dyn Error>> {
+    let lines: Vec<&str> = cues.iter().flat_map(|cue| cue.text.lines()).collect();
+    let mut translated = fill_lines(translator, to, &lines)?.into_iter();
+    Ok(cues
+        .iter()
+        .map(|cue| Cue {
+            start: cue.start,
+            end: cue.end,
+            text: translated
+                .by_ref()
+                .take(cue.text.lines().count())
+                .collect::<Vec<String>>()
+                .join("\n"),
+        })
+        .collect())
+}
+
+/// Empty lines mark pauses, they are kept as they are and not sent
+fn fill_lines(
+    translator: &Translator,
+    to: &str,
+    lines: &[&str],
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let texts: Vec<&str> = lines
+        .iter()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .collect();
+    let mut translated = translate_lines(translator, to, &texts)?.into_iter();
+    Ok(lines
+        .iter()
+        .map(|line| {
+            if line.trim().is_empty() {
+                String::new()
+            } else {
+                translated.next().unwrap_or_default()
+            }
+        })
+        .collect())
+}
+
+/// Each original line followed by its translation, synced lines share their timestamp
+pub fn interleave(original: &str, translated: &str) -> String {
+    let original_lrc = Lrc::parse(original);
+    let translated_lrc = Lrc::parse(translated);
+    if original_lrc.is_synced() && translated_lrc.is_synced() {
+        let mut lrc = original_lrc.clone();
+        lrc.lines.clear();
+        for (line, translation) in original_lrc.lines.into_iter().zip(translated_lrc.lines) {
+            let same = line.text == translation.text || translation.text.is_empty();
+            lrc.lines.push(line);
+            if !same {
+                lrc.lines.push(translation);
+            }
+        }
+        return lrc.to_string();
+    }
+    original
+        .lines()
+        .zip(translated.lines())
+        .flat_map(|(line, translation)| {
+            if line.trim() == translation.trim() {
+                vec![line]
+            } else {
+                vec![line, translation]
+            }
+        })
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_stays_out_of_the_queue() {
+        let translator = Translator {
+            backend: TranslationBackend::LibreTranslate,
+            libretranslate_url: "http://localhost:5000".into(),
+            libretranslate_key: Some("secret".into()),
+        };
+        let saved = serde_json::to_string(&translator).unwrap();
+        assert!(!saved.contains("secret"));
+        let restored: Translator = serde_json::from_str(&saved).unwrap();
+        assert_eq!(restored.libretranslate_url, "http://localhost:5000");
+        assert_eq!(restored.libretranslate_key, None);
+    }
 }
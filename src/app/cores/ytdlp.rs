@@ -1,10 +1,27 @@
+use crate::USERAGENT;
+use crate::app::cores::acoustid::Acoustid;
 use crate::app::cores::depen_manager::{Depen, get_path};
-use crate::app::cores::files::file_finder;
-use crate::app::cores::lrclib::lrclib_fetch;
-use crate::app::cores::{kugou, musicbrainz, url_checker};
+use crate::app::cores::files::{
+    self, change_ext, file_finder, remove_partial, writable_tag, written_file,
+};
+use crate::app::cores::kugou::Kugou;
+use crate::app::cores::lrclib::Lrclib;
+use crate::app::cores::lyrics_provider::{self, LyricsProvider};
+use crate::app::cores::musicbrainz::{self, SharedReview};
+use crate::app::cores::process::{ProcessHandle, run_streaming};
+use crate::app::cores::progress::{self, Phase, Progress, SharedProgress};
+use crate::app::cores::translate::{self, TranslationMode, Translator};
+use crate::app::cores::{library, string_cleaner, url_checker};
+use lofty::config::WriteOptions;
+use lofty::prelude::*;
+use lofty::probe::Probe;
+use lofty::tag::items::Timestamp;
+use lofty::tag::{ItemValue, TagItem};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::error::Error;
 use std::fs;
+use std::io;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
@@ -15,88 +32,342 @@ pub fn version_check(depen: &Depen) -> Option<String> {
     }
 }
 
-pub fn video_download(
-    link: String,
-    directory: String,
-    format: i8,
-    frag: i8,
-    sub: bool,
-    lang: &str,
-    auto_gen: bool,
-    cookies: Option<String>,
-    use_cookies: bool,
-    res: i32,
-    yt_dlp: PathBuf,
-) -> Result<String, Box<dyn Error>> {
-    let n = frag.to_string().to_owned();
-
-    let mut yt = Command::new(yt_dlp);
-    if let Some(cookie) = cookies
-        && use_cookies
-    {
-        yt.arg("--cookies").arg(cookie);
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Video {
+    pub link: String,
+    pub directory: String,
+    pub format: i8,
+    pub frags: i8,
+    pub sub: bool,
+    pub lang: String,
+    pub auto_gen: bool,
+    pub cookies: Option<String>,
+    pub use_cookies: bool,
+    pub res: i32,
+    pub yt_dlp: PathBuf,
+    /// Keep the subtitles as `.srt` next to the video
+    #[serde(default)]
+    pub write_srt: bool,
+    #[serde(default)]
+    pub ffmpeg: Option<PathBuf>,
+    /// Machine translate another subtitle when `lang` is missing
+    #[serde(default)]
+    pub translate_sub: bool,
+    #[serde(default)]
+    pub translator: Translator,
+}
+
+impl Video {
+    pub fn download(
+        self,
+        progress: &SharedProgress,
+        process: &ProcessHandle,
+    ) -> Result<String, Box<dyn Error>> {
+        let n = self.frags.to_string();
+
+        let mut yt = Command::new(&self.yt_dlp);
+        if let Some(cookie) = &self.cookies
+            && self.use_cookies
+        {
+            yt.arg("--cookies").arg(cookie);
+        }
+
+        let deno_path = get_path().deno;
+        if let Some(deno) = deno_path.to_str() {
+            yt.arg("--js-runtimes").arg(format!("deno:{}", deno));
+        }
+
+        yt.arg("--concurrent-fragments")
+            .arg(n)
+            .arg("--newline")
+            .arg("--progress-template")
+            .arg(progress::TEMPLATE)
+            .arg("--embed-thumbnail")
+            .arg("--embed-metadata")
+            .arg("--add-metadata")
+            .arg("--metadata-from-title")
+            .arg("%(title)s")
+            .arg("--parse-metadata")
+            .arg("title:%(title)s")
+            .arg("--parse-metadata")
+            .arg("uploader:%(artist)s")
+            .arg("--output")
+            .arg("%(title)s.%(ext)s")
+            .arg("--compat-options")
+            .arg("no-live-chat")
+            .current_dir(&self.directory);
+        // Auto generated subtitles repeat their rolling lines, they are cleaned and muxed here
+        let own_subs = self.sub && (self.auto_gen || self.write_srt);
+        if own_subs {
+            if self.auto_gen {
+                yt.arg("--write-auto-subs");
+            } else {
+                yt.arg("--write-subs");
+            }
+            yt.arg("--sub-format")
+                .arg("vtt/best")
+                .arg("--convert-subs")
+                .arg("vtt")
+                .arg("--sub-lang")
+                .arg(&self.lang);
+        } else if self.sub {
+            yt.arg("--embed-subs").arg("--sub-lang").arg(&self.lang);
+        }
+        // The info json tells which subtitles exist, it is not embedded into mkv
+        if self.sub && self.translate_sub {
+            yt.arg("--write-info-json")
+                .arg("--no-embed-info-json")
+                .arg("--no-write-playlist-metafiles");
+        }
+
+        let res = self.res;
+        if self.format == 1 {
+            yt.arg("-f")
+                .arg(format!("bestvideo[height<={res}]+bestaudio"));
+        } else if self.format == 2 {
+            yt.arg("-f")
+                .arg(format!("bv*[ext=mp4][height<={res}]+ba[ext=m4a]"));
+        }
+        yt.arg(&self.link);
+        let mut written = vec![];
+        let output = run_streaming(&mut yt, process, |line| {
+            progress.lock().unwrap().update(line);
+            if let Some(file) = written_file(line) {
+                written.push(file);
+            }
+        })
+        .inspect_err(|_| {
+            if process.is_cancelled() {
+                remove_partial(Path::new(&self.directory), &written);
+            }
+        })?;
+
+        let log = String::from_utf8_lossy(&output.stdout);
+        let filename = &log
+            .lines()
+            .filter(|x| x.starts_with("[Metadata] Adding metadata to"))
+            .map(|x| x.replace("[Metadata] Adding metadata to", ""))
+            .map(|x| x.trim().to_string())
+            .collect::<Vec<String>>()
+            .join(" ");
+        log::info!("{log}");
+
+        if output.status.success() {
+            log::warn!("{}", String::from_utf8_lossy(&output.stderr));
+            if self.sub {
+                Progress::set_phase(progress, Phase::Subtitles);
+                let videos = log
+                    .lines()
+                    .filter(|x| x.starts_with("[Metadata] Adding metadata to"))
+                    .filter_map(|x| x.split('"').nth(1));
+                for video in videos {
+                    process.check()?;
+                    let video = Path::new(&self.directory).join(video);
+                    if let Err(e) = self.add_subtitles(&video, own_subs) {
+                        log::error!("Fail to embed subtitles in {video:?}: {e}");
+                    }
+                }
+            }
+            Ok(filename.to_string())
+        } else {
+            log::error!("{}", String::from_utf8_lossy(&output.stderr));
+            Err(String::from_utf8_lossy(&output.stderr).into())
+        }
     }
 
-    let deno_path = get_path().deno;
-    if let Some(deno) = deno_path.to_str() {
-        yt.arg("--js-runtimes").arg(format!("deno:{}", deno));
+    /// Mux the subtitles of `video`, with a machine translation when its language is missing
+    fn add_subtitles(&self, video: &Path, own_subs: bool) -> Result<(), Box<dyn Error>> {
+        let stem = video
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .ok_or("Failed to extract filename stem")?;
+        let mut tracks = if own_subs {
+            self.downloaded_tracks(stem)?
+        } else {
+            vec![]
+        };
+        let info_file = video.with_extension("info.json");
+        if self.translate_sub && info_file.exists() {
+            let info = serde_json::from_str::<VideoInfo>(&fs::read_to_string(&info_file)?);
+            let _ = fs::remove_file(&info_file);
+            match info
+                .map_err(|e| e.into())
+                .and_then(|info| self.translated_track(video, &info))
+            {
+                Ok(Some(track)) => tracks.push(track),
+                Ok(None) => {}
+                Err(e) => log::error!("Fail to translate subtitles: {e}"),
+            }
+        }
+        if tracks.is_empty() {
+            log::info!("No subtitle to add to {video:?}");
+            return Ok(());
+        }
+        let result = self.mux(video, &tracks);
+        if !self.write_srt {
+            for track in &tracks {
+                let _ = fs::remove_file(&track.file);
+            }
+        }
+        result
     }
 
-    yt.arg("--concurrent-fragments")
-        .arg(n)
-        .arg("--embed-thumbnail")
-        .arg("--embed-metadata")
-        .arg("--add-metadata")
-        .arg("--metadata-from-title")
-        .arg("%(title)s")
-        .arg("--parse-metadata")
-        .arg("title:%(title)s")
-        .arg("--parse-metadata")
-        .arg("uploader:%(artist)s")
-        .arg("--output")
-        .arg("%(title)s.%(ext)s")
-        .arg("--compat-options")
-        .arg("no-live-chat")
-        .current_dir(directory);
-    if sub && auto_gen {
-        yt.arg("--write-auto-subs")
-            .arg("--embed-subs")
-            .arg("--sub-lang")
-            .arg(lang);
-    } else if sub {
-        yt.arg("--embed-subs").arg("--sub-lang").arg(lang);
+    /// Turn the `<video>.<lang>.vtt` yt-dlp left into srt files
+    fn downloaded_tracks(&self, stem: &str) -> Result<Vec<SubtitleTrack>, Box<dyn Error>> {
+        let mut tracks = vec![];
+        for entry in fs::read_dir(&self.directory)?.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let Some(lang) = name
+                .strip_prefix(&format!("{stem}."))
+                .and_then(|rest| rest.strip_suffix(".vtt"))
+            else {
+                continue;
+            };
+            let vtt = entry.path();
+            let mut cues = subtitle::parse(&fs::read_to_string(&vtt)?, SubtitleFormat::Vtt);
+            if self.auto_gen {
+                cues = lyrics::caption_cleaner(cues);
+            }
+            fs::remove_file(&vtt)?;
+            if cues.is_empty() {
+                continue;
+            }
+            let srt = vtt.with_extension("srt");
+            fs::write(&srt, subtitle::write(&cues, SubtitleFormat::Srt))?;
+            tracks.push(SubtitleTrack {
+                lang: lang.to_string(),
+                file: srt,
+                title: None,
+            });
+        }
+        tracks.sort_by(|a, b| a.lang.cmp(&b.lang));
+        Ok(tracks)
     }
 
-    if format == 1 {
-        yt.arg("-f")
-            .arg(format!("bestvideo[height<={res}]+bestaudio"));
-    } else if format == 2 {
-        yt.arg("-f")
-            .arg(format!("bv*[ext=mp4][height<={res}]+ba[ext=m4a]"));
+    /// Nothing when the video has the wanted language, else the best other subtitle translated
+    fn translated_track(
+        &self,
+        video: &Path,
+        info: &VideoInfo,
+    ) -> Result<Option<SubtitleTrack>, Box<dyn Error>> {
+        let available = if self.auto_gen {
+            &info.automatic_captions
+        } else {
+            &info.subtitles
+        };
+        if available
+            .as_ref()
+            .is_some_and(|subs| subs.contains_key(&self.lang))
+        {
+            return Ok(None);
+        }
+        let Some((source, entry, auto)) = info.best_subtitle() else {
+            return Err("The video has no subtitle".into());
+        };
+        log::info!("No {} subtitle, translating {source}", self.lang);
+        let mut cues = subtitle::parse(&entry.fetch()?, SubtitleFormat::Vtt);
+        if auto {
+            cues = lyrics::caption_cleaner(cues);
+        }
+        let cues = translate::translate_cues(&self.translator, &self.lang, &cues)?;
+        let srt = video.with_extension(format!("{}.srt", self.lang));
+        fs::write(&srt, subtitle::write(&cues, SubtitleFormat::Srt))?;
+        Ok(Some(SubtitleTrack {
+            lang: self.lang.clone(),
+            file: srt,
+            title: Some(format!("{} (machine translated from {source})", self.lang)),
+        }))
     }
-    let output = yt.arg(link).output()?;
-
-    let log = String::from_utf8_lossy(&output.stdout);
-    let filename = &log
-        .lines()
-        .filter(|x| x.starts_with("[Metadata] Adding metadata to"))
-        .map(|x| x.replace("[Metadata] Adding metadata to", ""))
-        .map(|x| x.trim().to_string())
-        .collect::<Vec<String>>()
-        .join(" ");
-    log::info!("{log}");
-
-    if output.status.success() {
-        log::warn!("{}", String::from_utf8_lossy(&output.stderr));
-        Ok(filename.to_string())
-    } else {
-        log::error!("{}", String::from_utf8_lossy(&output.stderr));
-        Err(String::from_utf8_lossy(&output.stderr).into())
+
+    /// Add `tracks` as subtitle streams of `video` in place
+    fn mux(&self, video: &Path, tracks: &[SubtitleTrack]) -> Result<(), Box<dyn Error>> {
+        let ext = video
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .ok_or("No extension on the video")?;
+        let muxed = video.with_extension(format!("azul-subs.{ext}"));
+        let mut ffmpeg = Command::new(self.ffmpeg.clone().unwrap_or("ffmpeg".into()));
+        ffmpeg.arg("-y").arg("-i").arg(video);
+        for track in tracks {
+            ffmpeg.arg("-i").arg(&track.file);
+        }
+        ffmpeg.arg("-map").arg("0");
+        for index in 1..=tracks.len() {
+            ffmpeg.arg("-map").arg(index.to_string());
+        }
+        let codec = if ext == "mp4" { "mov_text" } else { "srt" };
+        ffmpeg.arg("-c").arg("copy").arg("-c:s").arg(codec);
+        for (index, track) in tracks.iter().enumerate() {
+            ffmpeg
+                .arg(format!("-metadata:s:s:{index}"))
+                .arg(format!("language={}", track.lang));
+            if let Some(title) = &track.title {
+                ffmpeg
+                    .arg(format!("-metadata:s:s:{index}"))
+                    .arg(format!("title={title}"));
+            }
+        }
+        let output = ffmpeg.arg(&muxed).output()?;
+        if output.status.success() {
+            fs::rename(&muxed, video)?;
+            Ok(())
+        } else {
+            let _ = fs::remove_file(&muxed);
+            log::error!("{}", String::from_utf8_lossy(&output.stderr));
+            Err("ffmpeg could not add the subtitles".into())
+        }
+    }
+}
+
+struct SubtitleTrack {
+    lang: String,
+    file: PathBuf,
+    /// Shown by players next to the language
+    title: Option<String>,
+}
+
+/// What the video info json says about its subtitles
+#[derive(Debug, Deserialize)]
+struct VideoInfo {
+    subtitles: Option<HashMap<String, Vec<Entry>>>,
+    automatic_captions: Option<HashMap<String, Vec<Entry>>>,
+    language: Option<String>,
+}
+
+impl VideoInfo {
+    /// Subtitles made by people first, in the language of the video when there are several.
+    /// Auto generated ones only in the spoken language, the others are already translations.
+    /// Returns the language, the vtt to fetch and whether it is auto generated
+    fn best_subtitle(&self) -> Option<(&str, &Entry, bool)> {
+        fn vtt(subs: &[Entry]) -> Option<&Entry> {
+            subs.iter().find(|sub| sub.ext.trim() == "vtt")
+        }
+        if let Some(subs) = &self.subtitles {
+            let mut langs: Vec<&String> = subs.keys().filter(|lang| *lang != "live_chat").collect();
+            langs.sort_by_key(|lang| (Some(lang.as_str()) != self.language.as_deref(), *lang));
+            if let Some((lang, entry)) = langs
+                .into_iter()
+                .find_map(|lang| vtt(&subs[lang]).map(|entry| (lang, entry)))
+            {
+                return Some((lang, entry, false));
+            }
+        }
+        let captions = self.automatic_captions.as_ref()?;
+        let (lang, subs) = captions
+            .iter()
+            .find(|(lang, _)| lang.ends_with("-orig"))
+            .or_else(|| {
+                let lang = self.language.as_ref()?;
+                captions.get_key_value(lang)
+            })?;
+        Some((lang.trim_end_matches("-orig"), vtt(subs)?, true))
     }
 }
 use crate::app::cores::cover::{self, square_crop_to_bytes, to_png_bytes};
 use crate::app::cores::lyrics;
+use crate::app::cores::subtitle::{self, SubtitleFormat};
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Music {
     pub link: String,
     pub directory: String,
@@ -116,15 +387,199 @@ pub struct Music {
     pub sanitize_lyrics: bool,
     pub yt_dlp: PathBuf,
     pub keep_lrc: bool,
+    #[serde(default = "default_lyrics_order")]
+    pub lyrics_order: Vec<String>,
+    #[serde(default)]
+    pub word_synced: bool,
+    #[serde(default)]
+    pub translation: TranslationMode,
+    /// Backend and server, taken from the config when the job is queued
+    #[serde(default)]
+    pub translator: Translator,
+    #[serde(default = "default_duration_tolerance")]
+    pub duration_tolerance: u32,
+    /// Save the synced lyrics as `.srt` next to the music, for lyric videos
+    #[serde(default)]
+    pub write_srt: bool,
+    /// Identify the tracks by their sound before searching MusicBrainz by title
+    #[serde(default)]
+    pub acoustid: bool,
+    /// Key and server, taken from the config when the job is queued. The key is kept
+    /// out of queue.json, a restored job reads it from the config again
+    #[serde(skip)]
+    pub acoustid_key: String,
+    #[serde(default)]
+    pub acoustid_url: String,
+    /// The user reviews the MusicBrainz match of every track
+    #[serde(default)]
+    pub review: bool,
+    /// What the job waits on, a restored job starts with a new one
+    #[serde(skip)]
+    pub review_state: SharedReview,
+}
+
+fn default_duration_tolerance() -> u32 {
+    lyrics_provider::DEFAULT_DURATION_TOLERANCE
+}
+
+fn default_lyrics_order() -> Vec<String> {
+    lyrics_provider::DEFAULT_ORDER
+        .iter()
+        .map(|source| source.to_string())
+        .collect()
 }
 
-use serde::Deserialize;
 #[derive(Debug, Deserialize)]
 pub struct InfoJson {
     #[serde(rename = "_type")]
     pub filetype: String,
     // pub title: String,
     pub subtitles: Option<HashMap<String, Vec<Entry>>>,
+    /// Read on its own so odd metadata never loses the track
+    #[serde(skip)]
+    pub track: TrackInfo,
+}
+
+/// Music metadata YouTube Music, Bandcamp, SoundCloud and others put in the info json
+#[derive(Debug, Default, Deserialize)]
+pub struct TrackInfo {
+    pub track: Option<String>,
+    pub track_number: Option<u32>,
+    pub artist: Option<String>,
+    pub artists: Option<Vec<String>>,
+    pub creator: Option<String>,
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    pub album_artists: Option<Vec<String>>,
+    pub disc_number: Option<u32>,
+    pub release_year: Option<u16>,
+    /// `YYYYMMDD`
+    pub release_date: Option<String>,
+    pub genre: Option<String>,
+    pub genres: Option<Vec<String>>,
+    pub playlist_index: Option<u32>,
+    pub playlist_title: Option<String>,
+    pub n_entries: Option<u32>,
+    pub uploader: Option<String>,
+    pub channel: Option<String>,
+}
+
+impl TrackInfo {
+    /// Credited artists first. A channel is only an artist when youtube made it for one,
+    /// those are named `Artist - Topic`
+    fn artist(&self) -> Option<String> {
+        self.artists
+            .as_ref()
+            .filter(|artists| !artists.is_empty())
+            .map(|artists| artists.join(", "))
+            .or_else(|| self.artist.clone())
+            .or_else(|| self.creator.clone())
+            .or_else(|| {
+                [&self.uploader, &self.channel]
+                    .into_iter()
+                    .flatten()
+                    .find_map(|name| name.trim().strip_suffix(" - Topic"))
+                    .map(str::to_string)
+            })
+            .map(|artist| string_cleaner::clean_artist(&artist))
+            .filter(|artist| !artist.is_empty())
+    }
+
+    fn album_artist(&self) -> Option<String> {
+        self.album_artists
+            .as_ref()
+            .filter(|artists| !artists.is_empty())
+            .map(|artists| artists.join(", "))
+            .or_else(|| self.album_artist.clone())
+            .map(|artist| string_cleaner::clean_artist(&artist))
+            .filter(|artist| !artist.is_empty())
+    }
+
+    /// Track number, the playlist position only when the playlist is the album
+    fn track_number(&self) -> Option<(u32, Option<u32>)> {
+        if let Some(number) = self.track_number {
+            return Some((number, None));
+        }
+        let album = self.album.as_ref()?.to_lowercase();
+        let playlist = self.playlist_title.as_ref()?.to_lowercase();
+        if playlist.contains(&album) {
+            Some((self.playlist_index?, self.n_entries))
+        } else {
+            None
+        }
+    }
+
+    fn release_date(&self) -> Option<Timestamp> {
+        fn part<T: std::str::FromStr>(
+            date: Option<&str>,
+            range: std::ops::Range<usize>,
+        ) -> Option<T> {
+            date?.get(range)?.parse().ok()
+        }
+        let date = self.release_date.as_deref().filter(|date| date.len() == 8);
+        Some(Timestamp {
+            year: self.release_year.or_else(|| part(date, 0..4))?,
+            month: part(date, 4..6),
+            day: part(date, 6..8),
+            hour: None,
+            minute: None,
+            second: None,
+        })
+    }
+
+    /// Write what the site knows into `music_file`, over the channel name yt-dlp used as artist
+    pub fn embed(&self, music_file: &Path) -> Result<(), Box<dyn Error>> {
+        let mut tagged_file = Probe::open(music_file)?.read()?;
+        let tag = writable_tag(&mut tagged_file).ok_or("Fail to open tag")?;
+        if let Some(track) = self.track.as_ref().filter(|track| !track.is_empty()) {
+            tag.set_title(track.clone());
+        }
+        match self.artist() {
+            Some(artist) => tag.set_artist(artist),
+            None => {
+                if let Some(artist) = tag
+                    .artist()
+                    .map(|artist| string_cleaner::clean_artist(&artist))
+                {
+                    tag.set_artist(artist);
+                }
+            }
+        }
+        if let Some(album) = self.album.as_ref().filter(|album| !album.is_empty()) {
+            tag.set_album(album.clone());
+        }
+        if let Some(album_artist) = self.album_artist() {
+            tag.insert_text(ItemKey::AlbumArtist, album_artist);
+        }
+        if let Some(date) = self.release_date() {
+            tag.set_date(date);
+            if date.month.is_some() {
+                tag.insert_text(ItemKey::ReleaseDate, date.to_string());
+            }
+        }
+        if let Some((number, total)) = self.track_number() {
+            tag.set_track(number);
+            if let Some(total) = total {
+                tag.set_track_total(total);
+            }
+        }
+        if let Some(disc) = self.disc_number {
+            tag.set_disk(disc);
+        }
+        let genres = match (&self.genres, &self.genre) {
+            (Some(genres), _) if !genres.is_empty() => genres.clone(),
+            (_, Some(genre)) if !genre.is_empty() => vec![genre.clone()],
+            _ => vec![],
+        };
+        if !genres.is_empty() {
+            tag.remove_key(ItemKey::Genre);
+            for genre in genres {
+                tag.push(TagItem::new(ItemKey::Genre, ItemValue::Text(genre)));
+            }
+        }
+        tag.save_to_path(music_file, WriteOptions::default())?;
+        Ok(())
+    }
 }
 #[derive(Debug, Deserialize)]
 pub struct Entry {
@@ -132,16 +587,19 @@ pub struct Entry {
     pub url: String,
 }
 
+impl Entry {
+    pub fn fetch(&self) -> Result<String, Box<dyn Error>> {
+        let mut response = ureq::get(&self.url)
+            .header("User-Agent", USERAGENT)
+            .call()?;
+        Ok(response.body_mut().read_to_string()?)
+    }
+}
+
 pub fn get_all_music_title_and_playlist(
     path: &Path,
-) -> Result<
-    (
-        HashMap<String, Option<HashMap<String, Vec<Entry>>>>,
-        Option<String>,
-    ),
-    Box<dyn Error>,
-> {
-    let mut titles: HashMap<String, Option<HashMap<String, Vec<Entry>>>> = HashMap::new();
+) -> Result<(HashMap<String, InfoJson>, Option<String>), Box<dyn Error>> {
+    let mut titles: HashMap<String, InfoJson> = HashMap::new();
     let mut playlist: Option<String> = None;
     let reader = fs::read_dir(path)?;
 
@@ -150,14 +608,16 @@ pub fn get_all_music_title_and_playlist(
         if let Some(file) = item.file_name().and_then(|s| s.to_str())
             && file.contains(".info.json")
         {
-            if let Ok(infojson) = serde_json::from_str::<InfoJson>(&fs::read_to_string(&item)?) {
+            let data = fs::read_to_string(&item)?;
+            if let Ok(mut infojson) = serde_json::from_str::<InfoJson>(&data) {
+                infojson.track = serde_json::from_str(&data).unwrap_or_else(|e| {
+                    log::warn!("Failed to read track metadata of {item:?}: {e}");
+                    TrackInfo::default()
+                });
                 if infojson.filetype == "playlist" {
                     playlist = Some(file.trim_end_matches(".info.json").to_string());
                 } else {
-                    titles.insert(
-                        file.trim_end_matches(".info.json").to_string(),
-                        infojson.subtitles,
-                    );
+                    titles.insert(file.trim_end_matches(".info.json").to_string(), infojson);
                 }
                 fs::remove_file(item)?;
             } else {
@@ -171,7 +631,70 @@ pub fn get_all_music_title_and_playlist(
 }
 
 impl Music {
-    pub fn download(self) -> Result<String, Box<dyn Error>> {
+    pub fn download(
+        self,
+        progress: &SharedProgress,
+        process: &ProcessHandle,
+    ) -> Result<String, Box<dyn Error>> {
+        // Every job works in its own folder so it never picks up files from the user
+        // directory or from another download running next to it
+        let staging = files::staging_dir(Path::new(&self.directory))?;
+        log::info!("Staging directory: {staging:?}");
+        let result = self.download_staged(&staging, progress, process);
+        // Music that could not be moved out is the only copy, the folder stays for the user
+        let left = library::audio_files(&staging);
+        if !left.is_empty() {
+            let kept = format!(
+                "{} tracks could not be moved, they are kept in {staging:?}",
+                left.len()
+            );
+            log::error!("{kept}");
+            return match result {
+                Ok(_) => Err(kept.into()),
+                Err(e) => Err(format!("{e}, {kept}").into()),
+            };
+        }
+        if let Err(e) = fs::remove_dir_all(&staging) {
+            log::error!("Fail to remove staging directory {staging:?}: {e}");
+        }
+        result
+    }
+
+    /// Move a finished track and its sidecars into the user directory. A sidecar that
+    /// fails is logged and left behind, the music still gets out
+    fn move_out(&self, music_file: &Path) -> io::Result<PathBuf> {
+        let moved = files::move_unique(music_file, Path::new(&self.directory))?;
+        // Sidecars follow the name the music file got in the user directory
+        let mut sidecars = vec![(
+            lyrics::sidecar_path(music_file, &self.lang_code),
+            lyrics::sidecar_path(&moved, &self.lang_code),
+        )];
+        // `.lrc` is also left when the tag could not take the lyrics, anything else
+        // yt-dlp left is removed with the staging directory
+        let mut exts = vec!["lrc", "srt"];
+        // The `.jpg` is only ours when the cover did not fit in the tag
+        if !cover::has_cover(&moved) {
+            exts.push("jpg");
+        }
+        for ext in exts {
+            sidecars.push((change_ext(music_file, ext), change_ext(&moved, ext)));
+        }
+        for (sidecar, to) in sidecars {
+            if sidecar.exists()
+                && let Err(e) = files::move_file(&sidecar, &to)
+            {
+                log::error!("Fail to move {sidecar:?} to {to:?}: {e}");
+            }
+        }
+        Ok(moved)
+    }
+
+    fn download_staged(
+        self,
+        staging: &Path,
+        progress: &SharedProgress,
+        process: &ProcessHandle,
+    ) -> Result<String, Box<dyn Error>> {
         let format_name = match self.format {
             1 => "opus",
             2 => "flac",
@@ -182,10 +705,11 @@ impl Music {
         };
         let n = self.frags.to_string();
         log::info!("{}", n);
+        let staging_dir = staging.to_string_lossy().into_owned();
 
         let mut yt = Command::new(&self.yt_dlp);
 
-        if let Some(cookie) = self.cookies
+        if let Some(cookie) = &self.cookies
             && self.use_cookies
         {
             yt.arg("--cookies").arg(cookie);
@@ -197,6 +721,9 @@ impl Music {
 
         yt.arg("--concurrent-fragments")
             .arg(&n)
+            .arg("--newline")
+            .arg("--progress-template")
+            .arg(progress::TEMPLATE)
             .arg("-x")
             .arg("--audio-quality")
             .arg("0")
@@ -208,15 +735,15 @@ impl Music {
             .arg("%(title)s")
             .arg("--parse-metadata")
             .arg("title:%(title)s")
-            .arg("--parse-metadata")
-            .arg("uploader:%(artist)s")
+            // No uploader as artist here, `TrackInfo` only takes the channel of a
+            // `Artist - Topic` channel when the info json credits nobody
             .arg("--output")
             .arg("%(title)s.%(ext)s")
             .arg("--compat-options")
             .arg("no-live-chat")
             .arg("--write-info-json")
             .arg("--no-write-playlist-metafiles")
-            .current_dir(&self.directory);
+            .current_dir(staging);
 
         if self.lyrics && self.lyric_auto {
             yt.arg("--write-auto-subs");
@@ -224,67 +751,78 @@ impl Music {
             yt.arg("--sub-langs").arg(&self.lang_code);
         }
 
-        match url_checker::playlist_check(&self.link) {
-            url_checker::UrlStatus::Playlist => {
-                let mut yt_x = Command::new(&self.yt_dlp);
-                yt_x.arg("--skip-download")
-                    .arg("--write-info-json")
-                    .arg("--playlist-items")
-                    .arg("0")
-                    .arg("--output")
-                    .arg("playlist%(title)s.%(ext)s")
-                    .arg("--write-thumbnail")
-                    .current_dir(&self.directory)
-                    .arg(&self.link);
-                let output = yt_x.output()?;
-                let log = String::from_utf8(output.stdout)?;
-                log::info!("{}", log);
-            }
-            _ => {}
+        if let url_checker::UrlStatus::Playlist = url_checker::playlist_check(&self.link) {
+            let mut yt_x = Command::new(&self.yt_dlp);
+            yt_x.arg("--skip-download")
+                .arg("--write-info-json")
+                .arg("--playlist-items")
+                .arg("0")
+                .arg("--output")
+                .arg("playlist%(title)s.%(ext)s")
+                .arg("--write-thumbnail")
+                .current_dir(staging)
+                .arg(&self.link);
+            let output = run_streaming(&mut yt_x, process, |_| {})?;
+            let log = String::from_utf8(output.stdout)?;
+            log::info!("{}", log);
         }
 
         yt.arg(&self.link);
-        let output = yt.output()?;
+        let output = run_streaming(&mut yt, process, |line| {
+            progress.lock().unwrap().update(line)
+        })?;
 
         let log = String::from_utf8(output.stdout)?;
         log::info!("{}", log);
 
-        let (filenames_from_json_info, play) =
-            get_all_music_title_and_playlist(Path::new(&self.directory))?;
+        let (filenames_from_json_info, play) = get_all_music_title_and_playlist(staging)?;
 
         let mut filenames = "".to_string();
         let mut album_cover: Option<Vec<u8>> = None;
 
         if self.use_playlist_cover
             && let Some(name) = &play
+            && let Some(raw_image) = file_finder(&staging_dir, name, &["jpg", "jpeg", "png"])
         {
-            match file_finder(&self.directory, &name, &["jpg", "jpeg", "png"]) {
-                Some(raw_image) => {
-                    album_cover = if self.crop_cover {
-                        Some(square_crop_to_bytes(&raw_image)?)
-                    } else {
-                        Some(to_png_bytes(&raw_image)?)
-                    };
-                }
-                None => {}
-            }
+            album_cover = if self.crop_cover {
+                Some(square_crop_to_bytes(&raw_image)?)
+            } else {
+                Some(to_png_bytes(&raw_image)?)
+            };
+        }
+        let total = filenames_from_json_info.len() as u32;
+        let acoustid = if self.musicbrainz && self.acoustid {
+            Acoustid::new(&self.acoustid_key, &self.acoustid_url)
+        } else {
+            None
+        };
+        if self.musicbrainz
+            && let Ok(mut progress) = progress.lock()
+        {
+            progress.matched = Some((0, 0));
         }
-        for i in filenames_from_json_info {
+        for (index, i) in filenames_from_json_info.into_iter().enumerate() {
+            process.check()?;
+            if let Ok(mut progress) = progress.lock() {
+                progress.title = Some(i.0.clone());
+                progress.playlist_index = Some(index as u32 + 1);
+                progress.playlist_count = Some(total);
+            }
             let extension = format!(".{}", format_name);
             let filename = i.0;
-            filenames.push_str(&format!(" \"{filename}\" "));
 
             log::info!("filename: {filename}");
 
-            let music_file = Path::new(&self.directory).join(format!("{}{}", filename, extension));
+            let music_file = staging.join(format!("{}{}", filename, extension));
             log::info!("music dir: {music_file:?}");
 
             log::info!("Playlist name: {play:?}");
 
+            Progress::set_phase(progress, Phase::Thumbnail);
             match cover::embed(
                 self.crop_cover,
                 &music_file,
-                &self.directory,
+                &staging_dir,
                 &filename,
                 &album_cover,
             ) {
@@ -292,37 +830,89 @@ impl Music {
                 Err(e) => log::error!("embed cover fail: {e}"),
             }
 
+            Progress::set_phase(progress, Phase::Tagging);
+            match i.1.track.embed(&music_file) {
+                Ok(_) => log::info!("Embedded info json metadata"),
+                Err(e) => log::error!("Fail to embed info json metadata: {e}"),
+            }
+
             if self.musicbrainz {
-                let _ = musicbrainz::work(&music_file, self.sim_rate);
+                let tagged = if self.review {
+                    musicbrainz::review(&music_file, acoustid.as_ref(), &self.review_state, process)
+                } else {
+                    musicbrainz::work(&music_file, self.sim_rate, acoustid.as_ref())
+                };
+                let matched = match tagged {
+                    Ok(outcome) => {
+                        log::info!("Musicbrainz: {outcome}");
+                        outcome.is_matched()
+                    }
+                    Err(e) => {
+                        log::error!("Fail musicbrainz {e}");
+                        false
+                    }
+                };
+                if let Ok(mut progress) = progress.lock()
+                    && let Some((hits, tried)) = progress.matched.as_mut()
+                {
+                    *hits += u32::from(matched);
+                    *tried += 1;
+                }
+                process.check()?;
             }
-            if self.lyrics {
-                match lyrics::work(
-                    &filename,
+            let mut subtitles = i.1.subtitles;
+            let mut providers: Vec<Box<dyn LyricsProvider>> = vec![];
+            for source in &self.lyrics_order {
+                match source.as_str() {
+                    "kugou" if self.kugou_lyrics => providers.push(Box::new(Kugou {
+                        word_synced: self.word_synced,
+                    })),
+                    "lrclib" if self.lrclib => providers.push(Box::new(Lrclib)),
+                    "youtube" if self.lyrics => providers.push(Box::new(lyrics::Youtube {
+                        filename: filename.clone(),
+                        directory: staging_dir.clone(),
+                        sanitize: self.sanitize_lyrics,
+                        lang_code: self.lang_code.clone(),
+                        subtitles: subtitles.take(),
+                    })),
+                    _ => {}
+                }
+            }
+            if !providers.is_empty() {
+                Progress::set_phase(progress, Phase::Lyrics);
+                match lyrics_provider::apply(
+                    &providers,
                     &music_file,
-                    format_name,
-                    &self.directory,
-                    self.sanitize_lyrics,
                     &self.lang_code,
-                    i.1,
+                    self.translation,
+                    &self.translator,
                     self.keep_lrc,
+                    self.duration_tolerance,
                 ) {
-                    Ok(_) => log::info!("Lyrics from youtube embedded"),
-                    Err(e) => log::error!("Fail to use lyrics from youtube: {e}"),
+                    Ok(_) => log::info!("Lyrics embedded"),
+                    Err(e) => log::error!("Fail to embed lyrics: {e}"),
+                }
+                if self.write_srt {
+                    match subtitle::lyrics_srt(&music_file) {
+                        Ok(srt) => log::info!("Lyrics saved as {srt:?}"),
+                        Err(e) => log::error!("Fail to write srt: {e}"),
+                    }
                 }
             }
-            if self.lrclib {
-                let _ = lrclib_fetch(&music_file, &self.lang_code, self.keep_lrc);
-            }
-            if self.kugou_lyrics {
-                let _ = kugou::get(&music_file, &self.lang_code, self.keep_lrc);
+
+            // One track failing to move must not stop the others
+            let moved = match self.move_out(&music_file) {
+                Ok(moved) => moved,
+                Err(e) => {
+                    log::error!("Fail to move {music_file:?}: {e}");
+                    continue;
+                }
+            };
+            log::info!("Moved to {moved:?}");
+            if let Some(name) = moved.file_stem().and_then(|name| name.to_str()) {
+                filenames.push_str(&format!(" \"{name}\" "));
             }
         }
-        if !self.use_playlist_cover
-            && let Some(trash_cover) = play
-        {
-            let trash = file_finder(&self.directory, &trash_cover, &["jpg"]).unwrap();
-            let _ = std::fs::remove_file(&trash);
-        }
         if output.status.success() {
             log::warn!("{}", String::from_utf8_lossy(&output.stderr));
             Ok(filenames)
@@ -332,3 +922,29 @@ impl Music {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn artist_from_credits_or_topic_channel_only() {
+        let credited = TrackInfo {
+            artists: Some(vec!["A".into(), "B".into()]),
+            uploader: Some("A - Topic".into()),
+            ..Default::default()
+        };
+        assert_eq!(credited.artist().as_deref(), Some("A, B"));
+        let topic = TrackInfo {
+            uploader: Some("Band - Topic".into()),
+            ..Default::default()
+        };
+        assert_eq!(topic.artist().as_deref(), Some("Band"));
+        let channel = TrackInfo {
+            uploader: Some("Some Lyrics Channel".into()),
+            channel: Some("Some Lyrics Channel".into()),
+            ..Default::default()
+        };
+        assert_eq!(channel.artist(), None);
+    }
+}
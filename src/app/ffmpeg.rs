@@ -1,13 +1,16 @@
 use crate::app::cores::depen_manager::Depen;
 
-use crate::app::cores::notify::{button_sound, done_sound, fail_sound};
+use crate::app::cores::notify::button_sound;
+use crate::app::cores::process::{ProcessHandle, run_streaming};
+use crate::app::cores::queue::{JobKind, JobQueue, JobState};
+use crate::app::cores::subtitle::{self, SUBTITLE_FORMAT};
+use crate::app::share_view::job_status_view;
 use eframe::egui::{self, Color32};
 use rfd::FileDialog;
+use serde::{Deserialize, Serialize};
 use std::error::Error;
 use std::path::{Path, PathBuf};
 use std::process::Command;
-use std::sync::atomic::{AtomicI8, Ordering};
-use std::sync::{Arc, Mutex};
 
 static IMAGE_FORMAT: &[&str; 9] = &[
     "png", "bmp", "tif", "gif", "webp", "heic", "jpg", "avif", "jpeg",
@@ -21,12 +24,11 @@ static VIDEO_FORMAT: &[&str; 17] = &[
 ];
 pub struct Ffmpeg {
     pub out_directory: String,
-    pub status: Arc<AtomicI8>,
+    pub last_job: Option<u64>,
     pub format_in: String,
     pub format_out: String,
     pub input_file: String,
     compression: FFmpegCompression,
-    error_message: Arc<Mutex<String>>,
 }
 
 impl Default for Ffmpeg {
@@ -34,19 +36,15 @@ impl Default for Ffmpeg {
         Self {
             input_file: String::new(),
             out_directory: "".to_string(),
-            status: Arc::new(AtomicI8::new(0)), // 0 = nothing / 1 = pending / 2 = Done / 3 = Fail
+            last_job: None,
             format_in: String::new(),
             format_out: String::from("None"),
             compression: FFmpegCompression::None,
-            error_message: Arc::new(Mutex::new(String::new())),
         }
     }
 }
 
 impl Ffmpeg {
-    fn start_download_status(&mut self) {
-        self.status.store(1, Ordering::Relaxed);
-    }
     fn toggle_compression(
         &mut self,
         ui: &mut egui::Ui,
@@ -87,7 +85,8 @@ impl Ffmpeg {
             };
         }
     }
-    pub fn ui(&mut self, ui: &mut egui::Ui, depen: &Depen) {
+    pub fn ui(&mut self, ui: &mut egui::Ui, depen: &Depen, queue: &JobQueue) {
+        let job = self.last_job.and_then(|id| queue.get(id));
         ui.horizontal(|ui| {
             ui.horizontal_wrapped(|ui| {
                 ui.label("Output: ");
@@ -104,6 +103,10 @@ impl Ffmpeg {
                         for format in IMAGE_FORMAT {
                             self.format_out_button(ui, format);
                         }
+                    } else if SUBTITLE_FORMAT.contains(&self.format_in.as_str()) {
+                        for format in SUBTITLE_FORMAT {
+                            self.format_out_button(ui, format);
+                        }
                     } else {
                         self.format_out_button(ui, "Nothing");
                     }
@@ -177,11 +180,15 @@ impl Ffmpeg {
                 .clicked()
             {
                 let mut filter = Vec::with_capacity(
-                    AUDIO_FORMAT.len() + VIDEO_FORMAT.len() + IMAGE_FORMAT.len(),
+                    AUDIO_FORMAT.len()
+                        + VIDEO_FORMAT.len()
+                        + IMAGE_FORMAT.len()
+                        + SUBTITLE_FORMAT.len(),
                 );
                 filter.extend_from_slice(AUDIO_FORMAT);
                 filter.extend_from_slice(VIDEO_FORMAT);
                 filter.extend_from_slice(IMAGE_FORMAT);
+                filter.extend_from_slice(SUBTITLE_FORMAT);
                 let path = FileDialog::new()
                     .set_directory(&self.out_directory)
                     .add_filter("Media", &filter[..])
@@ -216,50 +223,24 @@ impl Ffmpeg {
                     log::info!("No file selected.");
                 }
             };
-            if self.status.load(Ordering::Relaxed) != 1 {
-                if ui.button("Convert").clicked() {
+            if ui.button("Convert").clicked() {
+                let _ = button_sound();
+                let convert = Convert {
+                    input: self.input_file.clone(),
+                    directory: self.out_directory.clone(),
+                    format_out: self.format_out.clone(),
+                    ffmpeg: depen.ffmpeg.clone(),
+                    compression: self.compression.clone(),
+                };
+                self.last_job = Some(queue.push(JobKind::Convert(convert)));
+            }
+            if job_status_view::is_running(&job) {
+                if ui.button("Cancel").clicked()
+                    && let Some(id) = self.last_job
+                {
                     let _ = button_sound();
-                    self.start_download_status();
-
-                    let input = self.input_file.clone();
-                    let directory = self.out_directory.clone();
-                    let format_out = self.format_out.clone();
-                    let progress = self.status.clone();
-                    let ffmpeg = depen.ffmpeg.clone();
-                    let compression = self.compression.clone();
-                    let error_message_clone = Arc::clone(&self.error_message);
-
-                    tokio::task::spawn(async move {
-                        match ffmpeg_cli(&input, directory, format_out, ffmpeg, compression) {
-                            Ok(_) => {
-                                progress.store(2, Ordering::Relaxed);
-                                let _ = done_sound("ffmpeg convertor", input);
-                            }
-                            Err(e) => {
-                                *error_message_clone.lock().unwrap() = e.to_string();
-                                progress.store(3, Ordering::Relaxed);
-                                let _ = fail_sound("ffmpeg convertor");
-                            }
-                        }
-                    });
-                }
-            } else if self.status.load(Ordering::Relaxed) == 1 {
-                if ui.button("Cancel").clicked() {
-                    #[cfg(target_os = "windows")]
-                    {
-                        let _ = button_sound();
-                        let _ = Command::new("taskkill")
-                            .args(&["/IM", "ffmpeg.exe", "/F"])
-                            .output();
-                    }
-                    #[cfg(target_os = "linux")]
-                    {
-                        let _ = button_sound();
-                        let _ = Command::new("pkill").arg("ffmpeg").output();
-                    }
+                    queue.cancel(id);
                 }
-            }
-            if self.status.load(Ordering::Relaxed) == 1 {
                 ui.spacing();
                 ui.separator();
                 ui.horizontal_wrapped(|ui| {
@@ -268,31 +249,73 @@ impl Ffmpeg {
                         egui::RichText::new("This may take awhile").color(Color32::LIGHT_GRAY),
                     );
                 });
-            } else if self.status.load(Ordering::Relaxed) == 3 {
-                ui.spacing();
-                ui.separator();
-                egui::ScrollArea::vertical()
-                    .max_height(100.0)
-                    .show(ui, |ui| {
-                        ui.label(
-                            egui::RichText::new(format!("{}", self.error_message.lock().unwrap()))
-                                .color(Color32::LIGHT_RED)
-                                .size(16.0),
-                        );
-                    });
-            } else if self.status.load(Ordering::Relaxed) == 2 {
-                ui.colored_label(Color32::LIGHT_GREEN, "Done!");
-            };
+            } else if let Some(job) = &job {
+                match job.state {
+                    JobState::Queued => {
+                        ui.colored_label(Color32::LIGHT_BLUE, "Queued");
+                    }
+                    JobState::Done => {
+                        ui.colored_label(Color32::LIGHT_GREEN, "Done!");
+                    }
+                    JobState::Cancelled => {
+                        ui.colored_label(Color32::LIGHT_YELLOW, "Cancelled");
+                    }
+                    _ => {}
+                }
+            }
+            job_status_view::error(ui, &job);
         });
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Convert {
+    pub input: String,
+    pub directory: String,
+    pub format_out: String,
+    pub ffmpeg: Option<PathBuf>,
+    pub compression: FFmpegCompression,
+}
+
+impl Convert {
+    pub fn run(self, process: &ProcessHandle) -> Result<String, Box<dyn Error>> {
+        // Subtitles are plain text, ffmpeg has no LRC and drops the ASS styling
+        let is_subtitle = |path: &str| {
+            Path::new(path)
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| SUBTITLE_FORMAT.contains(&ext.to_lowercase().as_str()))
+        };
+        if is_subtitle(&self.input) {
+            if !SUBTITLE_FORMAT.contains(&self.format_out.as_str()) {
+                return Err("A subtitle can only be converted to another subtitle".into());
+            }
+            subtitle::convert_file(
+                Path::new(&self.input),
+                Path::new(&self.directory),
+                &self.format_out,
+            )?;
+            return Ok(self.input);
+        }
+        ffmpeg_cli(
+            &self.input,
+            self.directory,
+            self.format_out,
+            self.ffmpeg,
+            self.compression,
+            process,
+        )?;
+        Ok(self.input)
+    }
+}
+
 fn ffmpeg_cli(
     input: &str,
     directory: String,
     format_out: String,
     ffmpeg: Option<PathBuf>,
     compression: FFmpegCompression,
+    process: &ProcessHandle,
 ) -> Result<(), Box<dyn Error>> {
     if input.is_empty() {
         return Err("No input".into());
@@ -331,10 +354,13 @@ fn ffmpeg_cli(
         FFmpegCompression::None => {}
     }
 
-    cli_build
-        .arg(format!("{}.{}", filename, format_out))
-        .current_dir(directory);
-    let output = cli_build.output()?;
+    let output_file = format!("{}.{}", filename, format_out);
+    cli_build.arg(&output_file).current_dir(&directory);
+    let output = run_streaming(&mut cli_build, process, |_| {}).inspect_err(|_| {
+        if process.is_cancelled() {
+            let _ = std::fs::remove_file(Path::new(&directory).join(&output_file));
+        }
+    })?;
 
     if output.status.success() {
         Ok(())
@@ -344,8 +370,8 @@ fn ffmpeg_cli(
     }
 }
 
-#[derive(Debug, Clone)]
-enum FFmpegCompression {
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum FFmpegCompression {
     Libx264(u8),
     JpgCompression(u8),
     PngCompression(u8),
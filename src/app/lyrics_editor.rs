@@ -0,0 +1,312 @@
+use crate::app::cores::depen_manager::Depen;
+use crate::app::cores::files::change_ext;
+use crate::app::cores::library::AUDIO_FORMAT;
+use crate::app::cores::lrc::{self, Lrc, LrcLine};
+use crate::app::cores::player::{self, AudioPlayer};
+use crate::app::cores::{language, lyrics};
+use eframe::egui::{self, Color32};
+use rfd::FileDialog;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+
+/// How far the seek buttons jump, in milliseconds
+const SEEK_STEP: u64 = 5000;
+
+type Preparing = Receiver<Result<(PathBuf, bool), String>>;
+
+#[derive(Default)]
+pub struct LyricsEditor {
+    pub music_file: String,
+    /// The lyrics as LRC, the stamp and shift tools rewrite it
+    text: String,
+    /// Next line the stamp button times
+    cursor: usize,
+    /// The lyrics were synced or a line got stamped, until then they are saved plain
+    synced: bool,
+    /// Milliseconds the shift button moves every line by
+    offset: i64,
+    keep_lrc: bool,
+    audio: Option<AudioPlayer>,
+    preparing: Option<Preparing>,
+    message: Option<(Color32, String)>,
+}
+
+impl LyricsEditor {
+    fn load(&mut self, ffmpeg: Option<PathBuf>) {
+        let music_file = PathBuf::from(&self.music_file);
+        self.audio = None;
+        self.cursor = 0;
+        self.message = None;
+        let lyrics = match lyrics::read(&music_file) {
+            Ok(Some(lyrics)) => lyrics,
+            Ok(None) => std::fs::read_to_string(change_ext(&music_file, "lrc")).unwrap_or_default(),
+            Err(e) => {
+                log::error!("Fail to read lyrics {e}");
+                self.message = Some((Color32::LIGHT_RED, format!("Fail to read lyrics: {e}")));
+                String::new()
+            }
+        };
+        let (text, synced) = lrc::normalize(&lyrics);
+        self.synced = synced;
+        self.text = match (text, synced) {
+            (synced, true) => synced,
+            // Plain lyrics start at zero and get their time from the stamp button
+            (plain, false) => Lrc {
+                tags: vec![],
+                lines: plain
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map(|line| LrcLine::new(0, line))
+                    .collect(),
+            }
+            .to_string(),
+        };
+
+        let (sender, receiver) = mpsc::channel();
+        self.preparing = Some(receiver);
+        tokio::task::spawn_blocking(move || {
+            let result = player::playable(&music_file, ffmpeg).map_err(|e| e.to_string());
+            let _ = sender.send(result);
+        });
+    }
+
+    /// Pick up the audio once ffmpeg is done with it
+    fn poll_audio(&mut self) {
+        let Some(receiver) = &self.preparing else {
+            return;
+        };
+        let Ok(result) = receiver.try_recv() else {
+            return;
+        };
+        self.preparing = None;
+        match result.and_then(|(file, temporary)| {
+            AudioPlayer::open(file, temporary).map_err(|e| e.to_string())
+        }) {
+            Ok(audio) => self.audio = Some(audio),
+            Err(e) => {
+                log::error!("Fail to open audio {e}");
+                self.message = Some((Color32::LIGHT_RED, format!("Fail to play: {e}")));
+            }
+        }
+    }
+
+    fn position(&self) -> u64 {
+        self.audio
+            .as_ref()
+            .map(|audio| audio.position())
+            .unwrap_or(0)
+    }
+
+    fn seek(&self, ms: u64) {
+        if let Some(audio) = &self.audio {
+            audio.seek(ms);
+        }
+    }
+
+    /// Time the line under the cursor and move to the next one.
+    /// Later lines never stay before it so the order of the lines is kept
+    fn stamp(&mut self, at: u64) {
+        let mut lrc = Lrc::parse(&self.text);
+        let Some(line) = lrc.lines.get_mut(self.cursor) else {
+            return;
+        };
+        let delta = at as i64 - line.time as i64;
+        line.time = at;
+        for word in line.words.iter_mut() {
+            word.time = word.time.saturating_add_signed(delta);
+        }
+        for later in lrc.lines.iter_mut().skip(self.cursor + 1) {
+            later.time = later.time.max(at);
+            for word in later.words.iter_mut() {
+                word.time = word.time.max(at);
+            }
+        }
+        // Earlier lines timed after `at` now come after the stamped one, the cursor
+        // follows the line to where it is sorted
+        let mut order: Vec<usize> = (0..lrc.lines.len()).collect();
+        order.sort_by_key(|index| lrc.lines[*index].time);
+        let stamped = order
+            .iter()
+            .position(|index| *index == self.cursor)
+            .unwrap_or(self.cursor);
+        let lines = order
+            .iter()
+            .map(|index| lrc.lines[*index].clone())
+            .collect();
+        lrc.lines = lines;
+        self.cursor = stamped + 1;
+        self.synced = true;
+        self.text = lrc.to_string();
+    }
+
+    fn shift(&mut self) {
+        let mut lrc = Lrc::parse(&self.text);
+        lrc.shift(self.offset);
+        self.text = lrc.to_string();
+    }
+
+    fn save(&mut self) {
+        let music_file = Path::new(&self.music_file);
+        let lrc = Lrc::parse(&self.text);
+        // Plain lyrics only got their placeholder times, they are no synced lyrics yet
+        let lyrics = if self.synced || lrc.lines.iter().any(|line| line.time > 0) {
+            self.text.clone()
+        } else {
+            lrc.plain()
+        };
+        let lang = language::detect(&lyrics);
+        self.message = match lyrics::embed(music_file, &lyrics, lang.as_deref(), self.keep_lrc) {
+            Ok(_) => Some((Color32::LIGHT_GREEN, "Saved".to_string())),
+            Err(e) => {
+                log::error!("Fail to save lyrics {e}");
+                Some((Color32::LIGHT_RED, format!("Fail to save: {e}")))
+            }
+        };
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui, depen: &Depen) {
+        self.poll_audio();
+        ui.vertical_centered(|ui| {
+            let file_label = ui.label("Music: ");
+            if ui
+                .text_edit_singleline(&mut self.music_file)
+                .labelled_by(file_label.id)
+                .clicked()
+            {
+                let mut dialog = FileDialog::new().add_filter("Music", AUDIO_FORMAT);
+                if let Some(parent) = Path::new(&self.music_file).parent() {
+                    dialog = dialog.set_directory(parent);
+                }
+                if let Some(p) = dialog.pick_file() {
+                    self.music_file = p.to_string_lossy().into_owned();
+                    self.load(depen.ffmpeg.clone());
+                } else {
+                    log::info!("No file selected.");
+                }
+            }
+        });
+        ui.separator();
+
+        let position = self.position();
+        let playing = self.audio.as_ref().is_some_and(|audio| audio.is_playing());
+        ui.horizontal(|ui| {
+            if let Some(audio) = &self.audio {
+                if playing {
+                    if ui.button("Pause").clicked() {
+                        audio.pause();
+                    }
+                } else if ui.button("Play").clicked()
+                    && let Err(e) = audio.play()
+                {
+                    log::error!("Fail to play {e}");
+                }
+                if ui.button("-5s").clicked() {
+                    self.seek(position.saturating_sub(SEEK_STEP));
+                }
+                if ui.button("+5s").clicked() {
+                    self.seek(position + SEEK_STEP);
+                }
+                ui.label(lrc::format_time(position));
+            } else if self.preparing.is_some() {
+                ui.spinner();
+                ui.label("Preparing audio");
+            }
+        });
+
+        let lrc = Lrc::parse(&self.text);
+        ui.horizontal(|ui| {
+            let stamp = ui
+                .add_enabled(self.cursor < lrc.lines.len(), egui::Button::new("Stamp"))
+                .on_hover_text("Time the marked line to now, Space does the same");
+            let space = ui.input(|i| i.key_pressed(egui::Key::Space))
+                && ui.ctx().memory(|memory| memory.focused().is_none());
+            if stamp.clicked() || (space && self.cursor < lrc.lines.len()) {
+                self.stamp(position);
+            }
+            if ui
+                .add_enabled(self.cursor > 0, egui::Button::new("Back"))
+                .clicked()
+            {
+                self.cursor -= 1;
+            }
+            ui.separator();
+            ui.add(
+                egui::DragValue::new(&mut self.offset)
+                    .range(-60000..=60000)
+                    .suffix(" ms"),
+            );
+            if ui
+                .button("Shift")
+                .on_hover_text("Move every line, a negative value shows the lyrics sooner")
+                .clicked()
+            {
+                self.shift();
+            }
+        });
+
+        // The line being sung is highlighted, the marked one is next to stamp
+        let lrc = Lrc::parse(&self.text);
+        let current = lrc.lines.iter().rposition(|line| line.time <= position);
+        let mut clicked = None;
+        egui::ScrollArea::vertical()
+            .max_height(300.0)
+            .show(ui, |ui| {
+                for (index, line) in lrc.lines.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        let marker = if index == self.cursor { "▶" } else { " " };
+                        ui.label(egui::RichText::new(marker).color(Color32::LIGHT_YELLOW));
+                        ui.label(
+                            egui::RichText::new(lrc::format_time(line.time))
+                                .color(Color32::GRAY)
+                                .size(16.0),
+                        );
+                        let mut text = egui::RichText::new(&line.text).size(18.0);
+                        if Some(index) == current {
+                            text = text.color(Color32::LIGHT_BLUE).strong();
+                            if playing {
+                                ui.scroll_to_cursor(Some(egui::Align::Center));
+                            }
+                        }
+                        if ui
+                            .add(egui::Label::new(text).sense(egui::Sense::click()))
+                            .on_hover_text("Play from this line and stamp again from here")
+                            .clicked()
+                        {
+                            clicked = Some((index, line.time));
+                        }
+                    });
+                }
+            });
+        if let Some((index, time)) = clicked {
+            self.cursor = index;
+            self.seek(time);
+        }
+
+        egui::CollapsingHeader::new("LRC").show(ui, |ui| {
+            ui.add(
+                egui::TextEdit::multiline(&mut self.text)
+                    .code_editor()
+                    .desired_rows(8),
+            );
+        });
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.keep_lrc, "Keep lrc")
+                .on_hover_text("Also save a lrc file next to the music");
+            if ui
+                .add_enabled(!self.music_file.is_empty(), egui::Button::new("Save"))
+                .clicked()
+            {
+                self.save();
+            }
+            if let Some((color, message)) = &self.message {
+                ui.colored_label(*color, message);
+            }
+        });
+        if playing {
+            ui.ctx()
+                .request_repaint_after(std::time::Duration::from_millis(50));
+        }
+    }
+}
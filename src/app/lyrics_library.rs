@@ -0,0 +1,139 @@
+use crate::app::cores::config;
+use crate::app::cores::library::{LibraryLyrics, Outcome, SharedReport};
+use crate::app::cores::lyrics_provider::DEFAULT_DURATION_TOLERANCE;
+use crate::app::cores::notify::button_sound;
+use crate::app::cores::queue::{JobKind, JobQueue};
+use crate::app::cores::translate::Translator;
+use crate::app::share_view::{job_status_view, progress_view};
+use eframe::egui::{self, Color32};
+use rfd::FileDialog;
+
+pub struct LyricsLibrary {
+    pub directory: String,
+    pub last_job: Option<u64>,
+    pub dry_run: bool,
+    pub sidecar_only: bool,
+    pub report: SharedReport,
+}
+
+impl Default for LyricsLibrary {
+    fn default() -> Self {
+        Self {
+            directory: dirs::audio_dir()
+                .map(|path| path.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            last_job: None,
+            dry_run: false,
+            sidecar_only: false,
+            report: SharedReport::default(),
+        }
+    }
+}
+
+impl LyricsLibrary {
+    pub fn ui(&mut self, ui: &mut egui::Ui, queue: &JobQueue) {
+        let job = self.last_job.and_then(|id| queue.get(id));
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.dry_run, "Dry run")
+                .on_hover_text("Only show what would be found, nothing is written");
+            ui.checkbox(&mut self.sidecar_only, "Write .lrc only")
+                .on_hover_text("Save lyrics next to the music and leave the tags alone");
+            job_status_view::status(ui, &job);
+        });
+        ui.label(
+            egui::RichText::new(
+                "Sources, language and translation follow the Music-dl lyrics settings",
+            )
+            .color(Color32::DARK_GRAY)
+            .size(16.0),
+        );
+        ui.separator();
+        ui.vertical_centered(|ui| {
+            let dir_label = ui.label("Library: ");
+            if ui
+                .text_edit_singleline(&mut self.directory)
+                .labelled_by(dir_label.id)
+                .clicked()
+            {
+                let path = FileDialog::new()
+                    .set_directory(&self.directory)
+                    .pick_folder();
+
+                if let Some(p) = path {
+                    self.directory = p.to_string_lossy().into_owned();
+                } else {
+                    log::info!("No file selected.");
+                }
+            };
+            if !job_status_view::is_running(&job) && ui.button("Start").clicked() {
+                let _ = button_sound();
+                // Read the config now so the Music-dl settings changed since start apply
+                let configs = match config::load_config(&config::get_config_file_path()) {
+                    Ok(config) => config,
+                    Err(e) => {
+                        log::error!("Fail to read config {e}");
+                        config::Config::default()
+                    }
+                };
+                let translator = Translator::from_config(&configs);
+                let music_dl = configs.music_dl;
+                let library = LibraryLyrics {
+                    directory: self.directory.clone(),
+                    lyrics_order: music_dl.lyrics_order.unwrap_or_default(),
+                    lrclib: music_dl.liblrc.unwrap_or(false),
+                    kugou_lyrics: music_dl.kugou_lyrics.unwrap_or(false),
+                    word_synced: music_dl.word_synced.unwrap_or(false),
+                    lang_code: configs.universal.language.unwrap_or("en".into()),
+                    translation: music_dl.translation.unwrap_or_default(),
+                    translator,
+                    duration_tolerance: music_dl
+                        .duration_tolerance
+                        .unwrap_or(DEFAULT_DURATION_TOLERANCE),
+                    dry_run: self.dry_run,
+                    sidecar_only: self.sidecar_only,
+                    report: self.report.clone(),
+                };
+                self.last_job = Some(queue.push(JobKind::Library(library)));
+            }
+            if job_status_view::is_running(&job) {
+                if ui.button("Cancel").clicked()
+                    && let Some(id) = self.last_job
+                {
+                    let _ = button_sound();
+                    queue.cancel(id);
+                }
+                if let Some(job) = &job {
+                    progress_view::show(ui, &job.progress.lock().unwrap());
+                }
+            }
+            job_status_view::error(ui, &job);
+        });
+        let report = self.report.lock().unwrap();
+        if report.is_empty() {
+            return;
+        }
+        ui.separator();
+        egui::ScrollArea::vertical()
+            .max_height(300.0)
+            .show(ui, |ui| {
+                for entry in report.iter() {
+                    let color = match entry.outcome {
+                        Outcome::Found { .. } => Color32::LIGHT_GREEN,
+                        Outcome::NotFound => Color32::LIGHT_YELLOW,
+                        Outcome::Skipped(_) => Color32::GRAY,
+                        Outcome::Failed(_) => Color32::LIGHT_RED,
+                    };
+                    ui.horizontal(|ui| {
+                        ui.colored_label(color, entry.outcome.to_string());
+                        let name = entry
+                            .file
+                            .file_name()
+                            .map(|name| name.to_string_lossy())
+                            .unwrap_or_default();
+                        ui.label(egui::RichText::new(name).size(16.0))
+                            .on_hover_text(entry.file.to_string_lossy());
+                    });
+                }
+            });
+    }
+}
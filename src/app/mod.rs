@@ -1,5 +1,10 @@
+pub mod cli;
 pub mod cores;
 pub mod ffmpeg;
+pub mod lyrics_editor;
+pub mod lyrics_library;
 pub mod music_dl;
+pub mod queue_view;
 pub mod share_view;
+pub mod tag_editor;
 pub mod video_dl;
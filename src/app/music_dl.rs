@@ -1,23 +1,18 @@
 use crate::app::cores::depen_manager::Depen;
+use crate::app::cores::queue::{JobKind, JobQueue, JobState};
+use crate::app::cores::translate::{TranslationBackend, TranslationMode, Translator};
 use crate::app::cores::url_checker::{UrlStatus, playlist_check, remove_radio};
-use crate::app::cores::{
-    notify::{button_sound, done_sound, fail_sound},
-    ytdlp,
-};
+use crate::app::cores::{notify::button_sound, ytdlp};
 use crate::app::share_view::lang_widget::LangThing;
-use crate::app::share_view::url_status_view;
+use crate::app::share_view::{job_status_view, progress_view, url_status_view};
 use eframe::egui::{self, Color32};
 use rfd::FileDialog;
-use std::fs;
 use std::path::PathBuf;
-use std::process::Command;
-use std::sync::atomic::{AtomicI8, Ordering};
-use std::sync::{Arc, Mutex};
 
 pub struct MusicDownload {
     pub link: String,
     pub out_directory: String,
-    pub status: Arc<AtomicI8>,
+    pub last_job: Option<u64>,
     pub format: i8,
     pub lyrics: bool,
     pub frag: i8,
@@ -25,6 +20,10 @@ pub struct MusicDownload {
     pub auto_lyric: bool,
     pub sim_rate: i8,
     pub musicbrainz: bool,
+    pub musicbrainz_review: bool,
+    pub acoustid: bool,
+    pub acoustid_key: String,
+    pub acoustid_url: String,
     pub lrclib: bool,
     pub kugou_lyrics: bool,
     pub config_path: PathBuf,
@@ -36,10 +35,17 @@ pub struct MusicDownload {
     pub url_status: UrlStatus,
     pub disable_radio: bool,
     pub keep_lrc: bool,
-    error_message: Arc<Mutex<String>>,
+    pub lyrics_order: Vec<String>,
+    pub word_synced: bool,
+    pub translation: TranslationMode,
+    pub duration_tolerance: u32,
+    pub write_srt: bool,
+    pub translator: TranslationBackend,
+    pub libretranslate_url: String,
+    pub libretranslate_key: String,
 }
 
-use crate::app::cores::{config, files};
+use crate::app::cores::config;
 
 impl Default for MusicDownload {
     fn default() -> Self {
@@ -57,7 +63,7 @@ impl Default for MusicDownload {
         Self {
             link: String::new(),
             out_directory: default_directory,
-            status: Arc::new(AtomicI8::new(0)), // 0 = nothing / 1 = pending / 2 = Done / 3 = Fail
+            last_job: None,
             // The unwrap here is good me in the futur pls DONT "FIX" this. the value is guarantee to be Some
             format: configs.music_dl.format.unwrap(),
             lyrics: configs.music_dl.lyrics.unwrap(),
@@ -67,6 +73,10 @@ impl Default for MusicDownload {
             auto_lyric: configs.music_dl.auto_gen_sub.unwrap(),
             sim_rate: configs.music_dl.threshold.unwrap(),
             musicbrainz: configs.music_dl.musicbrainz.unwrap(),
+            musicbrainz_review: configs.music_dl.musicbrainz_review.unwrap(),
+            acoustid: configs.music_dl.acoustid.unwrap(),
+            acoustid_key: configs.music_dl.acoustid_key.unwrap_or_default(),
+            acoustid_url: configs.music_dl.acoustid_url.unwrap(),
             lrclib: configs.music_dl.liblrc.unwrap(),
             cookies: configs.universal.cookies,
             config_path: path,
@@ -77,15 +87,19 @@ impl Default for MusicDownload {
             url_status: UrlStatus::None,
             disable_radio: configs.music_dl.disable_radio.unwrap(),
             keep_lrc: configs.music_dl.keep_lrc.unwrap(),
-            error_message: Arc::new(Mutex::new(String::new())),
+            lyrics_order: configs.music_dl.lyrics_order.unwrap(),
+            word_synced: configs.music_dl.word_synced.unwrap(),
+            translation: configs.music_dl.translation.unwrap(),
+            duration_tolerance: configs.music_dl.duration_tolerance.unwrap(),
+            write_srt: configs.music_dl.write_srt.unwrap(),
+            translator: configs.universal.translator.unwrap(),
+            libretranslate_url: configs.universal.libretranslate_url.unwrap(),
+            libretranslate_key: configs.universal.libretranslate_key.unwrap_or_default(),
         }
     }
 }
 
 impl MusicDownload {
-    fn start_download_status(&mut self) {
-        self.status.store(1, Ordering::Relaxed);
-    }
     fn music_brainz_button(&mut self, ui: &mut egui::Ui) {
         ui.menu_button("Musicbrainz", |ui| {
             ui.horizontal(|ui| {
@@ -123,6 +137,55 @@ impl MusicDownload {
                     }
                 }
             }
+            let review = ui
+                .checkbox(&mut self.musicbrainz_review, "Review matches")
+                .on_hover_text("Pick the recording and release of every track yourself");
+            if review.changed() {
+                match config::modifier_config(&self.config_path, |cfg| {
+                    cfg.music_dl.musicbrainz_review = Some(self.musicbrainz_review)
+                }) {
+                    Ok(_) => {
+                        log::info!("Changed musicbrainz review");
+                    }
+                    Err(e) => {
+                        log::error!("Fail change musicbrainz review {e}");
+                    }
+                }
+            }
+            let acoustid = ui
+                .checkbox(&mut self.acoustid, "AcoustID fingerprint")
+                .on_hover_text("Identify the song by its sound, the title search is the fallback");
+            if acoustid.changed() {
+                match config::modifier_config(&self.config_path, |cfg| {
+                    cfg.music_dl.acoustid = Some(self.acoustid)
+                }) {
+                    Ok(_) => {
+                        log::info!("Changed acoustid");
+                    }
+                    Err(e) => {
+                        log::error!("Fail change acoustid {e}");
+                    }
+                }
+            }
+            if self.acoustid {
+                ui.label("API key");
+                let key = ui.text_edit_singleline(&mut self.acoustid_key);
+                ui.label("Server url");
+                let url = ui.text_edit_singleline(&mut self.acoustid_url);
+                if key.lost_focus() || url.lost_focus() {
+                    match config::modifier_config(&self.config_path, |cfg| {
+                        cfg.music_dl.acoustid_key = Some(self.acoustid_key.clone());
+                        cfg.music_dl.acoustid_url = Some(self.acoustid_url.clone())
+                    }) {
+                        Ok(_) => {
+                            log::info!("Changed acoustid server");
+                        }
+                        Err(e) => {
+                            log::error!("Fail change acoustid server {e}");
+                        }
+                    }
+                }
+            }
         });
     }
     fn format_button(&mut self, ui: &mut egui::Ui, name: &str, numbername: i8) {
@@ -193,10 +256,142 @@ impl MusicDownload {
         }
     }
 
-    pub fn ui(&mut self, ui: &mut egui::Ui, depen: &Depen) {
-        if self.format == 5 {
-            self.lyrics = false;
+    fn translation_button(&mut self, ui: &mut egui::Ui) {
+        ui.menu_button(format!("Translation: {}", self.translation), |ui| {
+            let modes = [
+                (TranslationMode::Off, "Keep the lyrics as found"),
+                (TranslationMode::Replace, "Embed only the translation"),
+                (
+                    TranslationMode::Interleave,
+                    "Each line followed by its translation",
+                ),
+                (
+                    TranslationMode::Sidecar,
+                    "Embed the original, save the translation as <name>.<lang>.lrc",
+                ),
+            ];
+            for (mode, hover) in modes {
+                let radio = ui
+                    .radio_value(&mut self.translation, mode, mode.to_string())
+                    .on_hover_text(hover);
+                if radio.changed() {
+                    match config::modifier_config(&self.config_path, |cfg| {
+                        cfg.music_dl.translation = Some(self.translation)
+                    }) {
+                        Ok(_) => {
+                            log::info!("Changed translation mode");
+                        }
+                        Err(e) => {
+                            log::error!("Fail change translation mode {e}");
+                        }
+                    }
+                }
+            }
+            ui.separator();
+            self.translator_button(ui);
+        });
+    }
+
+    fn translator_button(&mut self, ui: &mut egui::Ui) {
+        ui.menu_button(format!("Translator: {}", self.translator), |ui| {
+            for backend in [
+                TranslationBackend::Google,
+                TranslationBackend::LibreTranslate,
+                TranslationBackend::None,
+            ] {
+                if ui
+                    .radio_value(&mut self.translator, backend, backend.to_string())
+                    .changed()
+                {
+                    match config::modifier_config(&self.config_path, |cfg| {
+                        cfg.universal.translator = Some(self.translator)
+                    }) {
+                        Ok(_) => {
+                            log::info!("Changed translator");
+                        }
+                        Err(e) => {
+                            log::error!("Fail change translator {e}");
+                        }
+                    }
+                }
+            }
+            if self.translator == TranslationBackend::LibreTranslate {
+                ui.separator();
+                ui.label("Server url");
+                let url = ui.text_edit_singleline(&mut self.libretranslate_url);
+                ui.label("API key (optional)");
+                let key = ui.text_edit_singleline(&mut self.libretranslate_key);
+                if url.lost_focus() || key.lost_focus() {
+                    match config::modifier_config(&self.config_path, |cfg| {
+                        cfg.universal.libretranslate_url = Some(self.libretranslate_url.clone());
+                        cfg.universal.libretranslate_key = Some(self.libretranslate_key.clone())
+                    }) {
+                        Ok(_) => {
+                            log::info!("Changed libretranslate");
+                        }
+                        Err(e) => {
+                            log::error!("Fail change libretranslate {e}");
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    fn duration_tolerance_slider(&mut self, ui: &mut egui::Ui) {
+        let slider = egui::widgets::Slider::new(&mut self.duration_tolerance, 0..=30)
+            .text("Length tolerance (s)");
+        let response = ui
+            .add(slider)
+            .on_hover_cursor(egui::CursorIcon::Grab)
+            .on_hover_and_drag_cursor(egui::CursorIcon::Grabbing)
+            .on_hover_text(
+                "Lyrics made for a version longer or shorter than this are skipped, 0 accepts any",
+            );
+        if response.changed() {
+            match config::modifier_config(&self.config_path, |cfg| {
+                cfg.music_dl.duration_tolerance = Some(self.duration_tolerance)
+            }) {
+                Ok(_) => {
+                    log::info!("Changed duration tolerance");
+                }
+                Err(e) => {
+                    log::error!("Fail change duration tolerance {e}");
+                }
+            }
         }
+    }
+
+    fn lyrics_order_button(&mut self, ui: &mut egui::Ui) {
+        ui.menu_button("Source order", |ui| {
+            ui.label("The first source with a good match is used");
+            let mut moved = None;
+            for (index, source) in self.lyrics_order.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(index > 0, egui::Button::new("⬆")).clicked() {
+                        moved = Some(index);
+                    }
+                    ui.label(format!("{}. {source}", index + 1));
+                });
+            }
+            if let Some(index) = moved {
+                self.lyrics_order.swap(index, index - 1);
+                match config::modifier_config(&self.config_path, |cfg| {
+                    cfg.music_dl.lyrics_order = Some(self.lyrics_order.clone())
+                }) {
+                    Ok(_) => {
+                        log::info!("Changed lyrics order");
+                    }
+                    Err(e) => {
+                        log::error!("Fail change lyrics order {e}");
+                    }
+                }
+            }
+        });
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui, depen: &Depen, queue: &JobQueue) {
+        let job = self.last_job.and_then(|id| queue.get(id));
         ui.horizontal(|ui| {
             ui.menu_button("Setting", |ui| {
                 ui.menu_button("cookies", |ui| {
@@ -282,6 +477,8 @@ impl MusicDownload {
                     let lang_in = self.sub_lang.clone();
                     self.sub_lang = LangThing::lang_chooser(ui, lang_in);
 
+                    self.translation_button(ui);
+
                     ui.separator();
 
                     let youtube_lyrics = ui
@@ -334,6 +531,28 @@ impl MusicDownload {
                             }
                         }
                     }
+                    if self.kugou_lyrics {
+                        let word_synced = ui
+                            .checkbox(&mut self.word_synced, "Word-synced lyrics")
+                            .on_hover_text(
+                                "Embed per-word timing from kugou for karaoke capable players",
+                            );
+                        if word_synced.changed() {
+                            match config::modifier_config(&self.config_path, |cfg| {
+                                cfg.music_dl.word_synced = Some(self.word_synced)
+                            }) {
+                                Ok(_) => {
+                                    log::info!("Changed word_synced");
+                                }
+                                Err(e) => {
+                                    log::error!("Fail change word_synced {e}");
+                                }
+                            }
+                        }
+                    }
+                    ui.separator();
+                    self.lyrics_order_button(ui);
+                    self.duration_tolerance_slider(ui);
                     ui.separator();
                     let save_lrc = ui.checkbox(&mut self.keep_lrc, "Keep lrc").on_hover_text(
                         "Save a lrc file for lyrics while still embed lyrics as usual",
@@ -350,6 +569,21 @@ impl MusicDownload {
                             }
                         }
                     }
+                    let save_srt = ui.checkbox(&mut self.write_srt, "Save srt").on_hover_text(
+                        "Save synced lyrics as a srt subtitle next to the music, for lyric videos",
+                    );
+                    if save_srt.changed() {
+                        match config::modifier_config(&self.config_path, |cfg| {
+                            cfg.music_dl.write_srt = Some(self.write_srt)
+                        }) {
+                            Ok(_) => {
+                                log::info!("Changed srt behavior");
+                            }
+                            Err(e) => {
+                                log::error!("Fail change srt behavior {e}");
+                            }
+                        }
+                    }
                 });
                 self.music_brainz_button(ui);
 
@@ -369,18 +603,11 @@ impl MusicDownload {
                 }
                 if ui.button("Close").clicked() {}
             });
-            ui.label("Status: ");
-            if self.status.load(Ordering::Relaxed) == 1 {
-                ui.spinner();
-            } else if self.status.load(Ordering::Relaxed) == 2 {
-                ui.colored_label(Color32::LIGHT_GREEN, "Done!");
-            } else if self.status.load(Ordering::Relaxed) == 3 {
-                ui.colored_label(Color32::LIGHT_RED, "Fail!");
-            }
+            job_status_view::status(ui, &job);
         });
         ui.separator();
         ui.vertical_centered(|ui| {
-            if self.status.load(Ordering::Relaxed) == 1 {
+            if job_status_view::is_running(&job) {
                 url_status_view::show(ui, &self.url_status);
             }
             let link_label = ui.label("Link: ");
@@ -404,125 +631,78 @@ impl MusicDownload {
                 }
             };
 
-            if self.status.load(Ordering::Relaxed) != 1 {
-                if ui.button("Download").clicked() {
-                    if self.disable_radio {
-                        self.link = remove_radio(&self.link)
-                    }
-                    self.url_status = playlist_check(&self.link);
-                    let _ = button_sound();
-
-                    self.start_download_status();
-
-                    let link = self.link.clone();
-                    let directory = self.out_directory.clone();
-                    let format = self.format;
-                    let progress = self.status.clone();
-                    let lyrics = self.lyrics;
-                    let frags = self.frag;
-                    let lang_code = self.sub_lang.clone();
-                    let auto = self.auto_lyric;
-                    let brain = self.musicbrainz;
-                    let sim = self.sim_rate;
-                    let lrclib = self.lrclib;
-                    let cook = self.cookies.clone();
-                    let use_cook = self.use_cookies;
-                    let crop = self.crop_cover;
-                    let playlist_cover = self.use_playlist_cover;
-                    let sanitization = self.sanitize_lyrics;
-                    let yt_dlp_path = depen.yt_dlp.clone();
-                    let kugou = self.kugou_lyrics;
-                    let error_message_clone = Arc::clone(&self.error_message);
-                    let keep_lrc_clone = self.keep_lrc.clone();
-
-                    tokio::task::spawn(async move {
-                        let yt = ytdlp::Music {
-                            link,
-                            directory,
-                            format: format,
-                            lyrics: lyrics,
-                            frags: frags,
-                            lang_code: lang_code,
-                            lyric_auto: auto,
-                            sim_rate: sim,
-                            musicbrainz: brain,
-                            lrclib: lrclib,
-                            kugou_lyrics: kugou,
-                            cookies: cook,
-                            use_cookies: use_cook,
-                            crop_cover: crop,
-                            use_playlist_cover: playlist_cover,
-                            sanitize_lyrics: sanitization,
-                            yt_dlp: yt_dlp_path,
-                            keep_lrc: keep_lrc_clone,
-                        };
-                        match yt.download() {
-                            Ok(f) => {
-                                progress.store(2, Ordering::Relaxed);
-                                let _ = done_sound("Music Download", f);
-                            }
-                            Err(e) => {
-                                progress.store(3, Ordering::Relaxed);
-                                *error_message_clone.lock().unwrap() = e.to_string();
-                                let _ = fail_sound("Music Download");
-                            }
+            if ui.button("Download").clicked() {
+                let _ = button_sound();
+                // Several links can be pasted at once, each one becomes a job
+                let links: Vec<String> = self
+                    .link
+                    .split_whitespace()
+                    .map(|link| {
+                        if self.disable_radio {
+                            remove_radio(link)
+                        } else {
+                            link.to_string()
                         }
-                    });
-                }
-            } else if self.status.load(Ordering::Relaxed) == 1 {
-                if ui.button("Cancel").clicked() {
-                    #[cfg(target_os = "windows")]
-                    {
-                        let _ = button_sound();
-                        let _ = Command::new("taskkill")
-                            .args(&["/IM", "yt-dlp.exe", "/F"])
-                            .output();
-                    }
-                    #[cfg(target_os = "linux")]
-                    {
-                        let _ = button_sound();
-                        let _ = Command::new("pkill").arg("yt-dlp").output();
-                    }
+                    })
+                    .collect();
+                for link in links {
+                    self.url_status = playlist_check(&link);
+                    let yt = ytdlp::Music {
+                        link,
+                        directory: self.out_directory.clone(),
+                        format: self.format,
+                        lyrics: self.lyrics,
+                        frags: self.frag,
+                        lang_code: self.sub_lang.clone(),
+                        lyric_auto: self.auto_lyric,
+                        sim_rate: self.sim_rate,
+                        musicbrainz: self.musicbrainz,
+                        lrclib: self.lrclib,
+                        kugou_lyrics: self.kugou_lyrics,
+                        cookies: self.cookies.clone(),
+                        use_cookies: self.use_cookies,
+                        crop_cover: self.crop_cover,
+                        use_playlist_cover: self.use_playlist_cover,
+                        sanitize_lyrics: self.sanitize_lyrics,
+                        yt_dlp: depen.yt_dlp.clone(),
+                        keep_lrc: self.keep_lrc,
+                        lyrics_order: self.lyrics_order.clone(),
+                        word_synced: self.word_synced,
+                        duration_tolerance: self.duration_tolerance,
+                        translation: self.translation,
+                        translator: Translator {
+                            backend: self.translator,
+                            libretranslate_url: self.libretranslate_url.clone(),
+                            libretranslate_key: Some(self.libretranslate_key.clone())
+                                .filter(|key| !key.is_empty()),
+                        },
+                        write_srt: self.write_srt,
+                        acoustid: self.acoustid,
+                        acoustid_key: self.acoustid_key.clone(),
+                        acoustid_url: self.acoustid_url.clone(),
+                        review: self.musicbrainz && self.musicbrainz_review,
+                        review_state: Default::default(),
+                    };
+                    self.last_job = Some(queue.push(JobKind::Music(yt)));
                 }
+                self.link.clear();
             }
-            if self.status.load(Ordering::Relaxed) == 1 {
-                if let Some(file) = files::file_finder_no_name(&self.out_directory, &["part"]) {
-                    match fs::metadata(&file) {
-                        Ok(value) => {
-                            if let Some(f) = file.file_name().and_then(|f| f.to_str()) {
-                                ui.label(
-                                    egui::RichText::new(format!(
-                                        "{f}: {} Mib",
-                                        (value.len() / 1024 / 1024)
-                                    ))
-                                    .color(Color32::LIGHT_BLUE),
-                                );
-                            }
-                        }
-
-                        Err(e) => {
-                            if let Some(f) = file.file_name().and_then(|f| f.to_str()) {
-                                ui.label(
-                                    egui::RichText::new(format!("{f}: {}", e))
-                                        .color(Color32::LIGHT_BLUE),
-                                );
-                            }
-                        }
-                    }
+            if job_status_view::is_running(&job) {
+                if ui.button("Cancel").clicked()
+                    && let Some(id) = self.last_job
+                {
+                    let _ = button_sound();
+                    queue.cancel(id);
                 }
-            } else if self.status.load(Ordering::Relaxed) == 3 {
-                ui.spacing();
-                ui.separator();
-                egui::ScrollArea::vertical()
-                    .max_height(100.0)
-                    .show(ui, |ui| {
-                        ui.label(
-                            egui::RichText::new(format!("{}", self.error_message.lock().unwrap()))
-                                .color(Color32::LIGHT_RED)
-                                .size(16.0),
-                        );
-                    });
+                if let Some(job) = &job {
+                    progress_view::show(ui, &job.progress.lock().unwrap());
+                }
+            } else if let Some(job) = &job
+                && job.state != JobState::Queued
+            {
+                progress_view::matched(ui, &job.progress.lock().unwrap());
             }
+            job_status_view::error(ui, &job);
         });
     }
 }
@@ -0,0 +1,143 @@
+use crate::app::cores::config;
+use crate::app::cores::queue::{JobQueue, JobState};
+use crate::app::share_view::review_view;
+use eframe::egui::{self, Color32};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+pub struct QueueView {
+    pub concurrency: u8,
+    pub config_path: PathBuf,
+    /// Thumbnails of the MusicBrainz review
+    covers: HashMap<String, egui::TextureHandle>,
+}
+
+impl Default for QueueView {
+    fn default() -> Self {
+        let path = config::get_config_file_path();
+        let configs = match config::load_config(&path) {
+            Ok(config) => config,
+            Err(e) => {
+                log::error!("Fail to read config {e}");
+                config::Config::default()
+            }
+        };
+        Self {
+            // The value is guarantee to be Some after config repair
+            concurrency: configs.universal.concurrency.unwrap(),
+            config_path: path,
+            covers: HashMap::new(),
+        }
+    }
+}
+
+impl QueueView {
+    /// Shown whatever window is open, a music job can not go on until the user picks
+    pub fn review(&mut self, ui: &mut egui::Ui, queue: &JobQueue) {
+        // One question at a time, the other jobs keep waiting
+        let jobs = queue.jobs();
+        let Some((job, review)) = jobs
+            .iter()
+            .filter(|job| job.state == JobState::Running)
+            .find_map(|job| job.kind.pending_review().map(|review| (job, review)))
+        else {
+            self.covers.clear();
+            return;
+        };
+        egui::Window::new("MusicBrainz review")
+            .resizable(true)
+            .show(ui, |ui| {
+                ui.label(egui::RichText::new(job.kind.target()).size(16.0));
+                review_view::show(ui, review, &mut self.covers);
+            });
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui, queue: &JobQueue) {
+        ui.horizontal(|ui| {
+            let slider = ui.add(
+                egui::widgets::Slider::new(&mut self.concurrency, 1..=8).text("Parallel jobs"),
+            );
+            if slider.changed() {
+                queue.set_concurrency(self.concurrency as usize);
+                match config::modifier_config(&self.config_path, |cfg| {
+                    cfg.universal.concurrency = Some(self.concurrency)
+                }) {
+                    Ok(_) => {
+                        log::info!("Changed concurrency");
+                    }
+                    Err(e) => {
+                        log::error!("Fail change concurrency {e}");
+                    }
+                }
+            }
+            if ui.button("Clear finished").clicked() {
+                queue.clear_finished();
+            }
+        });
+        ui.separator();
+        let jobs = queue.jobs();
+        if jobs.is_empty() {
+            ui.label(egui::RichText::new("Nothing in the queue").color(Color32::DARK_GRAY));
+        }
+        egui::ScrollArea::vertical()
+            .max_height(400.0)
+            .show(ui, |ui| {
+                for job in jobs.iter().rev() {
+                    ui.horizontal(|ui| {
+                        match job.state {
+                            JobState::Queued => {
+                                ui.colored_label(Color32::LIGHT_BLUE, "Queued");
+                            }
+                            JobState::Running => {
+                                ui.spinner();
+                            }
+                            JobState::Done => {
+                                ui.colored_label(Color32::LIGHT_GREEN, "Done");
+                            }
+                            JobState::Failed => {
+                                ui.colored_label(Color32::LIGHT_RED, "Fail");
+                            }
+                            JobState::Cancelled => {
+                                ui.colored_label(Color32::LIGHT_YELLOW, "Cancelled");
+                            }
+                        }
+                        ui.label(egui::RichText::new(job.kind.name()).strong());
+                        ui.label(egui::RichText::new(job.kind.target()).size(16.0));
+                        if matches!(job.state, JobState::Failed | JobState::Cancelled)
+                            && ui.button("Retry").clicked()
+                        {
+                            queue.retry(job.id);
+                        }
+                        if job.state == JobState::Running && ui.button("Cancel").clicked() {
+                            queue.cancel(job.id);
+                        }
+                        if job.state != JobState::Running && ui.button("Remove").clicked() {
+                            queue.remove(job.id);
+                        }
+                    });
+                    if job.state == JobState::Running && job.kind.pending_review().is_some() {
+                        ui.colored_label(Color32::LIGHT_BLUE, "Waiting for a MusicBrainz pick");
+                    }
+                    if job.state == JobState::Running {
+                        let progress = job.progress.lock().unwrap().clone();
+                        if let Some(fraction) = progress.fraction {
+                            ui.add(egui::ProgressBar::new(fraction).desired_height(6.0));
+                        }
+                        ui.label(
+                            egui::RichText::new(progress.to_string())
+                                .color(Color32::LIGHT_GRAY)
+                                .size(14.0),
+                        );
+                    }
+                    if let Some(error) = &job.error {
+                        ui.label(
+                            egui::RichText::new(error)
+                                .color(Color32::LIGHT_RED)
+                                .size(14.0),
+                        );
+                    }
+                    ui.separator();
+                }
+            });
+    }
+}
@@ -0,0 +1,50 @@
+use eframe::egui::{self, Color32, Ui};
+
+use crate::app::cores::queue::{Job, JobState};
+
+pub fn status(ui: &mut Ui, job: &Option<Job>) {
+    ui.label("Status: ");
+    if let Some(job) = job {
+        match job.state {
+            JobState::Queued => {
+                ui.colored_label(Color32::LIGHT_BLUE, "Queued");
+            }
+            JobState::Running => {
+                ui.spinner();
+            }
+            JobState::Done => {
+                ui.colored_label(Color32::LIGHT_GREEN, "Done!");
+            }
+            JobState::Failed => {
+                ui.colored_label(Color32::LIGHT_RED, "Fail!");
+            }
+            JobState::Cancelled => {
+                ui.colored_label(Color32::LIGHT_YELLOW, "Cancelled");
+            }
+        }
+    }
+}
+
+pub fn error(ui: &mut Ui, job: &Option<Job>) {
+    if let Some(job) = job
+        && job.state == JobState::Failed
+        && let Some(error) = &job.error
+    {
+        ui.spacing();
+        ui.separator();
+        egui::ScrollArea::vertical()
+            .max_height(100.0)
+            .show(ui, |ui| {
+                ui.label(
+                    egui::RichText::new(error)
+                        .color(Color32::LIGHT_RED)
+                        .size(16.0),
+                );
+            });
+    }
+}
+
+pub fn is_running(job: &Option<Job>) -> bool {
+    job.as_ref()
+        .is_some_and(|job| job.state == JobState::Running)
+}
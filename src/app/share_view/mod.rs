@@ -1,2 +1,5 @@
+pub mod job_status_view;
 pub mod lang_widget;
+pub mod progress_view;
+pub mod review_view;
 pub mod url_status_view;
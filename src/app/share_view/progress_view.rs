@@ -0,0 +1,44 @@
+use eframe::egui::{self, Color32, Ui};
+
+use crate::app::cores::progress::{Phase, Progress};
+
+pub fn show(ui: &mut Ui, progress: &Progress) {
+    ui.horizontal_wrapped(|ui| {
+        ui.colored_label(Color32::LIGHT_BLUE, progress.phase.to_string());
+        if let Some(item) = progress.playlist_text() {
+            ui.label(egui::RichText::new(format!("Item {item}")).size(16.0));
+        }
+    });
+    if let Some(title) = &progress.title {
+        ui.label(
+            egui::RichText::new(title)
+                .color(Color32::LIGHT_GRAY)
+                .size(16.0),
+        );
+    }
+    match progress.fraction {
+        Some(fraction) => {
+            ui.add(egui::ProgressBar::new(fraction).show_percentage());
+        }
+        None if progress.phase != Phase::Waiting => {
+            ui.add(egui::ProgressBar::new(0.0).animate(true));
+        }
+        None => {}
+    }
+    matched(ui, progress);
+    ui.horizontal_wrapped(|ui| {
+        if let Some(speed) = progress.speed_text() {
+            ui.label(egui::RichText::new(speed).size(16.0));
+        }
+        if let Some(eta) = progress.eta_text() {
+            ui.label(egui::RichText::new(format!("ETA {eta}")).size(16.0));
+        }
+    });
+}
+
+/// How many tracks got MusicBrainz tags, also worth showing once the job is done
+pub fn matched(ui: &mut Ui, progress: &Progress) {
+    if let Some(text) = progress.matched_text() {
+        ui.label(egui::RichText::new(text).size(16.0));
+    }
+}
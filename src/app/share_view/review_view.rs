@@ -0,0 +1,115 @@
+use eframe::egui::{self, Color32, Ui};
+use std::collections::HashMap;
+
+use crate::app::cores::musicbrainz::{Candidate, Choice, SharedReview};
+
+const COVER_SIZE: f32 = 64.0;
+
+/// The MusicBrainz matches a music job waits on, `covers` keeps the decoded thumbnails
+pub fn show(ui: &mut Ui, review: &SharedReview, covers: &mut HashMap<String, egui::TextureHandle>) {
+    let mut review = review.lock().unwrap();
+    let Some(file) = review.file.clone() else {
+        return;
+    };
+    ui.separator();
+    ui.colored_label(Color32::LIGHT_BLUE, "Pick the MusicBrainz match");
+    ui.label(
+        egui::RichText::new(file)
+            .color(Color32::LIGHT_GRAY)
+            .size(16.0),
+    );
+    let mut choice = None;
+    egui::ScrollArea::vertical()
+        .max_height(320.0)
+        .show(ui, |ui| {
+            for (index, candidate) in review.candidates.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    match cover(ui, candidate, covers) {
+                        Some(texture) => {
+                            ui.add(egui::Image::new((
+                                texture.id(),
+                                egui::vec2(COVER_SIZE, COVER_SIZE),
+                            )));
+                        }
+                        None => {
+                            ui.add_space(COVER_SIZE + ui.spacing().item_spacing.x);
+                        }
+                    }
+                    ui.vertical(|ui| {
+                        ui.label(
+                            egui::RichText::new(format!(
+                                "{} - {}",
+                                candidate.artist, candidate.title
+                            ))
+                            .size(18.0),
+                        );
+                        ui.label(
+                            egui::RichText::new(&candidate.album)
+                                .color(Color32::LIGHT_GRAY)
+                                .size(16.0),
+                        );
+                        let details: Vec<&str> = [
+                            candidate.date.as_deref(),
+                            candidate.country.as_deref(),
+                            Some(candidate.kind.as_str()).filter(|kind| !kind.is_empty()),
+                        ]
+                        .into_iter()
+                        .flatten()
+                        .collect();
+                        ui.label(
+                            egui::RichText::new(format!(
+                                "{}  score {}",
+                                details.join(" · "),
+                                candidate.score
+                            ))
+                            .color(Color32::GRAY)
+                            .size(14.0),
+                        );
+                    });
+                    if ui.button("Use").clicked() {
+                        choice = Some(Choice::Pick(index));
+                    }
+                });
+            }
+        });
+    ui.horizontal(|ui| {
+        if ui
+            .button("Skip")
+            .on_hover_text("Leave this track as it is")
+            .clicked()
+        {
+            choice = Some(Choice::Skip);
+        }
+        if ui
+            .button("Skip all")
+            .on_hover_text("Leave the rest of the playlist as it is")
+            .clicked()
+        {
+            choice = Some(Choice::SkipAll);
+        }
+    });
+    if choice.is_some() {
+        review.file = None;
+        review.choice = choice;
+    }
+}
+
+fn cover(
+    ui: &Ui,
+    candidate: &Candidate,
+    covers: &mut HashMap<String, egui::TextureHandle>,
+) -> Option<egui::TextureHandle> {
+    if let Some(texture) = covers.get(&candidate.release_id) {
+        return Some(texture.clone());
+    }
+    let image = image::load_from_memory(candidate.cover.as_ref()?).ok()?;
+    let image = image.to_rgba8();
+    let size = [image.width() as usize, image.height() as usize];
+    let texture = ui.ctx().load_texture(
+        &candidate.release_id,
+        egui::ColorImage::from_rgba_unmultiplied(size, image.as_raw()),
+        egui::TextureOptions::default(),
+    );
+    covers.insert(candidate.release_id.clone(), texture.clone());
+    Some(texture)
+}
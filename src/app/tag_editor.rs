@@ -0,0 +1,263 @@
+use crate::app::cores::library::AUDIO_FORMAT;
+use crate::app::cores::tags::{self, CoverEdit, FIELDS, KEEP, TagEdit, TagInfo};
+use eframe::egui::{self, Color32};
+use rfd::FileDialog;
+use std::path::PathBuf;
+
+const COVER_SIZE: f32 = 128.0;
+const IMAGE_FORMAT: [&str; 4] = ["jpg", "jpeg", "png", "webp"];
+
+#[derive(Default)]
+pub struct TagEditor {
+    files: Vec<PathBuf>,
+    /// Tags as read, the pictures of the first file are shown
+    infos: Vec<TagInfo>,
+    /// One per `FIELDS`, `KEEP` where the files differ
+    values: Vec<String>,
+    lyrics: String,
+    cover: CoverEdit,
+    /// Thumbnail of the first picture of the first file, or of the new cover
+    texture: Option<egui::TextureHandle>,
+    message: Option<(Color32, String)>,
+}
+
+impl TagEditor {
+    fn open(&mut self, files: Vec<PathBuf>) {
+        self.message = None;
+        self.cover = CoverEdit::Keep;
+        self.texture = None;
+        self.infos.clear();
+        self.files.clear();
+        for file in files {
+            match tags::read(&file) {
+                Ok(info) => {
+                    self.infos.push(info);
+                    self.files.push(file);
+                }
+                Err(e) => {
+                    log::error!("Fail to read tags of {file:?} {e}");
+                    self.message =
+                        Some((Color32::LIGHT_RED, format!("Fail to read {file:?}: {e}")));
+                }
+            }
+        }
+        (self.values, self.lyrics) = tags::merge(&self.infos);
+    }
+
+    fn save(&mut self) {
+        let edit = TagEdit {
+            values: self.values.iter().cloned().map(Some).collect(),
+            cover: self.cover.clone(),
+            lyrics: Some(self.lyrics.clone()),
+        };
+        let mut failed = 0;
+        for file in &self.files {
+            if let Err(e) = tags::write(file, &edit) {
+                log::error!("Fail to save tags of {file:?} {e}");
+                failed += 1;
+            }
+        }
+        let saved = self.files.len() - failed;
+        // Read back what was written, `KEEP` fields show their merged value again
+        self.open(self.files.clone());
+        self.message = Some(if failed == 0 {
+            (Color32::LIGHT_GREEN, format!("Saved {saved} files"))
+        } else {
+            (
+                Color32::LIGHT_RED,
+                format!("Saved {saved} files, {failed} failed, see the logs"),
+            )
+        });
+    }
+
+    fn thumbnail(&mut self, ctx: &egui::Context) -> Option<egui::TextureHandle> {
+        if let Some(texture) = &self.texture {
+            return Some(texture.clone());
+        }
+        let data = match &self.cover {
+            CoverEdit::Replace(data) => data.as_slice(),
+            CoverEdit::Remove => return None,
+            CoverEdit::Keep => self.infos.first()?.pictures.first()?.data(),
+        };
+        let image = image::load_from_memory(data).ok()?.to_rgba8();
+        let size = [image.width() as usize, image.height() as usize];
+        let texture = ctx.load_texture(
+            "tag-editor-cover",
+            egui::ColorImage::from_rgba_unmultiplied(size, image.as_raw()),
+            egui::TextureOptions::default(),
+        );
+        self.texture = Some(texture.clone());
+        Some(texture)
+    }
+
+    fn pictures(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            match self.thumbnail(ui.ctx()) {
+                Some(texture) => {
+                    ui.add(egui::Image::new((
+                        texture.id(),
+                        egui::vec2(COVER_SIZE, COVER_SIZE),
+                    )));
+                }
+                None => {
+                    ui.label(egui::RichText::new("No cover").color(Color32::GRAY));
+                }
+            }
+            ui.vertical(|ui| {
+                if let Some(first) = self.infos.first() {
+                    for picture in &first.pictures {
+                        ui.label(
+                            egui::RichText::new(format!(
+                                "{:?}, {} KiB",
+                                picture.pic_type(),
+                                picture.data().len() / 1024
+                            ))
+                            .color(Color32::LIGHT_GRAY)
+                            .size(16.0),
+                        );
+                    }
+                }
+                if ui
+                    .button("Replace cover")
+                    .on_hover_text("Use an image as the front cover of every file")
+                    .clicked()
+                    && let Some(image) = FileDialog::new()
+                        .add_filter("Image", &IMAGE_FORMAT)
+                        .pick_file()
+                {
+                    match std::fs::read(&image) {
+                        Ok(data) => {
+                            self.cover = CoverEdit::Replace(data);
+                            self.texture = None;
+                        }
+                        Err(e) => {
+                            log::error!("Fail to read {image:?} {e}");
+                            self.message =
+                                Some((Color32::LIGHT_RED, format!("Fail to read image: {e}")));
+                        }
+                    }
+                }
+                if ui.button("Remove cover").clicked() {
+                    self.cover = CoverEdit::Remove;
+                    self.texture = None;
+                }
+                let picture = self
+                    .infos
+                    .first()
+                    .and_then(|info| info.pictures.first())
+                    .cloned();
+                if ui
+                    .add_enabled(picture.is_some(), egui::Button::new("Extract"))
+                    .on_hover_text("Save the cover of the first file as an image")
+                    .clicked()
+                    && let Some(picture) = picture
+                    && let Some(file) = self.files.first()
+                {
+                    let ext = tags::picture_ext(&picture);
+                    let name = file
+                        .with_extension(ext)
+                        .file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or_default();
+                    let mut dialog = FileDialog::new().set_file_name(name);
+                    if let Some(parent) = file.parent() {
+                        dialog = dialog.set_directory(parent);
+                    }
+                    if let Some(to) = dialog.save_file() {
+                        self.message = Some(match tags::extract_picture(&picture, &to) {
+                            Ok(_) => (Color32::LIGHT_GREEN, format!("Cover saved to {to:?}")),
+                            Err(e) => {
+                                log::error!("Fail to extract cover {e}");
+                                (Color32::LIGHT_RED, format!("Fail to extract: {e}"))
+                            }
+                        });
+                    }
+                }
+            });
+        });
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.vertical_centered(|ui| {
+            if ui.button("Open").clicked() {
+                let mut dialog = FileDialog::new().add_filter("Music", AUDIO_FORMAT);
+                if let Some(parent) = self.files.first().and_then(|file| file.parent()) {
+                    dialog = dialog.set_directory(parent);
+                }
+                if let Some(files) = dialog.pick_files() {
+                    self.open(files);
+                } else {
+                    log::info!("No file selected.");
+                }
+            }
+            match self.files.as_slice() {
+                [] => {}
+                [file] => {
+                    ui.label(
+                        egui::RichText::new(file.to_string_lossy())
+                            .color(Color32::LIGHT_GRAY)
+                            .size(16.0),
+                    );
+                }
+                files => {
+                    ui.label(
+                        egui::RichText::new(format!("{} files", files.len()))
+                            .color(Color32::LIGHT_GRAY)
+                            .size(16.0),
+                    )
+                    .on_hover_text(
+                        files
+                            .iter()
+                            .map(|file| file.to_string_lossy())
+                            .collect::<Vec<_>>()
+                            .join("\n"),
+                    );
+                }
+            }
+        });
+        if self.files.is_empty() {
+            return;
+        }
+        ui.separator();
+        egui::ScrollArea::vertical()
+            .max_height(500.0)
+            .show(ui, |ui| {
+                egui::Grid::new("tag_fields")
+                    .num_columns(2)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for ((_, name), value) in FIELDS.iter().zip(self.values.iter_mut()) {
+                            ui.label(*name);
+                            let edit = ui.text_edit_singleline(value);
+                            if value == KEEP {
+                                edit.on_hover_text(
+                                    "Differs between the files, left as is each keeps its own",
+                                );
+                            }
+                            ui.end_row();
+                        }
+                    });
+                ui.separator();
+                self.pictures(ui);
+                egui::CollapsingHeader::new("Lyrics").show(ui, |ui| {
+                    ui.add(
+                        egui::TextEdit::multiline(&mut self.lyrics)
+                            .code_editor()
+                            .desired_rows(8),
+                    );
+                });
+            });
+        ui.separator();
+        ui.horizontal(|ui| {
+            if ui.button("Save").clicked() {
+                self.save();
+            }
+            if ui.button("Revert").clicked() {
+                self.open(self.files.clone());
+            }
+            if let Some((color, message)) = &self.message {
+                ui.colored_label(*color, message);
+            }
+        });
+    }
+}
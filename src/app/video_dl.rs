@@ -1,25 +1,20 @@
 use crate::app::cores::depen_manager::Depen;
 use crate::app::cores::url_checker::{UrlStatus, playlist_check, remove_radio};
 use crate::app::share_view::lang_widget::LangThing;
-use crate::app::share_view::url_status_view;
+use crate::app::share_view::{job_status_view, progress_view, url_status_view};
 use eframe::egui::{self, Color32};
 use rfd::FileDialog;
-use std::fs;
-use std::process::Command;
-use std::sync::atomic::{AtomicI8, Ordering};
-use std::sync::{Arc, Mutex};
 
-use crate::app::cores::{
-    notify::{button_sound, done_sound, fail_sound},
-    ytdlp,
-};
+use crate::app::cores::queue::{JobKind, JobQueue};
+use crate::app::cores::translate::Translator;
+use crate::app::cores::{notify::button_sound, ytdlp};
 
 use std::path::PathBuf;
 
 pub struct VideoDownload {
     pub link: String,
     pub out_directory: String,
-    pub status: Arc<AtomicI8>,
+    pub last_job: Option<u64>,
     pub format: i8,
     pub frag: i8,
     pub subtitle: bool,
@@ -31,10 +26,11 @@ pub struct VideoDownload {
     pub res: i32,
     url_status: UrlStatus,
     disable_radio: bool,
-    error_message: Arc<Mutex<String>>,
+    write_srt: bool,
+    translate_sub: bool,
 }
 
-use crate::app::cores::{config, files};
+use crate::app::cores::config;
 
 impl Default for VideoDownload {
     fn default() -> Self {
@@ -52,7 +48,7 @@ impl Default for VideoDownload {
         Self {
             link: String::new(),
             out_directory: default_directory,
-            status: Arc::new(AtomicI8::new(0)), // 0 = nothing / 1 = pending / 2 = Done / 3 = Fail
+            last_job: None,
             // unless user self sabotage, the unwrap here is good. me in the futur pls DONT "FIX" this. the value is guarantee to be Some
             format: configs.video_dl.format.unwrap(),
             frag: configs.video_dl.fragments.unwrap(),
@@ -65,15 +61,13 @@ impl Default for VideoDownload {
             res: configs.video_dl.resolution.unwrap(),
             url_status: UrlStatus::None,
             disable_radio: configs.video_dl.disable_radio.unwrap(),
-            error_message: Arc::new(Mutex::new(String::new())),
+            write_srt: configs.video_dl.write_srt.unwrap(),
+            translate_sub: configs.video_dl.translate_sub.unwrap(),
         }
     }
 }
 
 impl VideoDownload {
-    fn start_download_status(&mut self) {
-        self.status.store(1, Ordering::Relaxed);
-    }
     fn format_button(&mut self, ui: &mut egui::Ui, name: &str, numbername: i8) {
         if self.format == numbername {
             if ui
@@ -167,7 +161,8 @@ impl VideoDownload {
             }
         }
     }
-    pub fn ui(&mut self, ui: &mut egui::Ui, depen: &Depen) {
+    pub fn ui(&mut self, ui: &mut egui::Ui, depen: &Depen, queue: &JobQueue) {
+        let job = self.last_job.and_then(|id| queue.get(id));
         ui.horizontal(|ui| {
             ui.menu_button("Setting", |ui| {
                 ui.menu_button("cookies", |ui| {
@@ -246,6 +241,38 @@ impl VideoDownload {
                         let lang_in = self.sub_lang.clone();
                         self.sub_lang = LangThing::lang_chooser(ui, lang_in);
                         self.auto_on(ui);
+                        let save_srt = ui.checkbox(&mut self.write_srt, "Save srt").on_hover_text(
+                            "Also keep the subtitles as srt files next to the video",
+                        );
+                        if save_srt.changed() {
+                            match config::modifier_config(&self.config_path, |cfg| {
+                                cfg.video_dl.write_srt = Some(self.write_srt)
+                            }) {
+                                Ok(_) => {
+                                    log::info!("Changed srt behavior");
+                                }
+                                Err(e) => {
+                                    log::error!("Fail change srt behavior {e}");
+                                }
+                            }
+                        }
+                        let translate = ui
+                            .checkbox(&mut self.translate_sub, "Translate when missing")
+                            .on_hover_text(
+                                "Machine translate another subtitle when the language is not available",
+                            );
+                        if translate.changed() {
+                            match config::modifier_config(&self.config_path, |cfg| {
+                                cfg.video_dl.translate_sub = Some(self.translate_sub)
+                            }) {
+                                Ok(_) => {
+                                    log::info!("Changed subtitle translation");
+                                }
+                                Err(e) => {
+                                    log::error!("Fail change subtitle translation {e}");
+                                }
+                            }
+                        }
                     } else {
                         ui.horizontal(|ui| {
                             ui.label("On/Off: ");
@@ -282,18 +309,11 @@ impl VideoDownload {
                 }
                 if ui.button("Close").clicked() {}
             });
-            ui.label("Status: ");
-            if self.status.load(Ordering::Relaxed) == 1 {
-                ui.spinner();
-            } else if self.status.load(Ordering::Relaxed) == 2 {
-                ui.colored_label(Color32::LIGHT_GREEN, "Done!");
-            } else if self.status.load(Ordering::Relaxed) == 3 {
-                ui.colored_label(Color32::LIGHT_RED, "Fail!");
-            }
+            job_status_view::status(ui, &job);
         });
         ui.separator();
         ui.vertical_centered(|ui| {
-            if self.status.load(Ordering::Relaxed) == 1 {
+            if job_status_view::is_running(&job) {
                 url_status_view::show(ui, &self.url_status);
             }
             let link_label = ui.label("Link: ");
@@ -317,100 +337,63 @@ impl VideoDownload {
                 }
             };
 
-            if self.status.load(Ordering::Relaxed) != 1 {
-                if ui.button("Download").clicked() {
-                    if self.disable_radio {
-                        self.link = remove_radio(&self.link)
-                    }
-                    self.url_status = playlist_check(&self.link);
-                    let _ = button_sound();
-                    self.start_download_status();
-
-                    let link = self.link.clone();
-                    let directory = self.out_directory.clone();
-                    let format = self.format;
-                    let frags = self.frag;
-                    let progress = self.status.clone();
-                    let subtile = self.subtitle;
-                    let lang = self.sub_lang.clone();
-                    let auto_gen = self.auto_sub;
-                    let cook = self.cookies.clone();
-                    let use_cook = self.use_cookies;
-                    let res = self.res;
-                    let yt_dlp = depen.yt_dlp.clone();
-                    let error_message_clone = Arc::clone(&self.error_message);
-
-                    tokio::task::spawn(async move {
-                        match ytdlp::video_download(
-                            link, directory, format, frags, subtile, &lang, auto_gen, cook,
-                            use_cook, res, yt_dlp,
-                        ) {
-                            Ok(f) => {
-                                progress.store(2, Ordering::Relaxed);
-                                let _ = done_sound("Video Download", f);
-                            }
-                            Err(e) => {
-                                progress.store(3, Ordering::Relaxed);
-                                *error_message_clone.lock().unwrap() = e.to_string();
-                                let _ = fail_sound("Video Download");
-                            }
+            if ui.button("Download").clicked() {
+                let _ = button_sound();
+                // Several links can be pasted at once, each one becomes a job
+                let links: Vec<String> = self
+                    .link
+                    .split_whitespace()
+                    .map(|link| {
+                        if self.disable_radio {
+                            remove_radio(link)
+                        } else {
+                            link.to_string()
                         }
-                    });
-                }
-            } else if self.status.load(Ordering::Relaxed) == 1 {
-                if ui.button("Cancel").clicked() {
-                    #[cfg(target_os = "windows")]
-                    {
-                        let _ = button_sound();
-                        let _ = Command::new("taskkill")
-                            .args(&["/IM", "yt-dlp.exe", "/F"])
-                            .output();
-                    }
-                    #[cfg(target_os = "linux")]
-                    {
-                        let _ = button_sound();
-                        let _ = Command::new("pkill").arg("yt-dlp").output();
+                    })
+                    .collect();
+                // The translator is set in the Music-dl window, read it now
+                let translator = match config::load_config(&self.config_path) {
+                    Ok(configs) => Translator::from_config(&configs),
+                    Err(e) => {
+                        log::error!("Fail to read config {e}");
+                        Translator::default()
                     }
+                };
+                for link in links {
+                    self.url_status = playlist_check(&link);
+                    let video = ytdlp::Video {
+                        link,
+                        directory: self.out_directory.clone(),
+                        format: self.format,
+                        frags: self.frag,
+                        sub: self.subtitle,
+                        lang: self.sub_lang.clone(),
+                        auto_gen: self.auto_sub,
+                        cookies: self.cookies.clone(),
+                        use_cookies: self.use_cookies,
+                        res: self.res,
+                        yt_dlp: depen.yt_dlp.clone(),
+                        write_srt: self.write_srt,
+                        ffmpeg: depen.ffmpeg.clone(),
+                        translate_sub: self.translate_sub,
+                        translator: translator.clone(),
+                    };
+                    self.last_job = Some(queue.push(JobKind::Video(video)));
                 }
+                self.link.clear();
             }
-            if self.status.load(Ordering::Relaxed) == 1 {
-                if let Some(file) = files::file_finder_no_name(&self.out_directory, &["part"]) {
-                    match fs::metadata(&file) {
-                        Ok(value) => {
-                            if let Some(f) = file.file_name().and_then(|f| f.to_str()) {
-                                ui.label(
-                                    egui::RichText::new(format!(
-                                        "{f}: {} Mib",
-                                        (value.len() / 1024 / 1024)
-                                    ))
-                                    .color(Color32::LIGHT_BLUE),
-                                );
-                            }
-                        }
-
-                        Err(e) => {
-                            if let Some(f) = file.file_name().and_then(|f| f.to_str()) {
-                                ui.label(
-                                    egui::RichText::new(format!("{f}: {}", e))
-                                        .color(Color32::LIGHT_BLUE),
-                                );
-                            }
-                        }
-                    }
+            if job_status_view::is_running(&job) {
+                if ui.button("Cancel").clicked()
+                    && let Some(id) = self.last_job
+                {
+                    let _ = button_sound();
+                    queue.cancel(id);
+                }
+                if let Some(job) = &job {
+                    progress_view::show(ui, &job.progress.lock().unwrap());
                 }
-            } else if self.status.load(Ordering::Relaxed) == 3 {
-                ui.spacing();
-                ui.separator();
-                egui::ScrollArea::vertical()
-                    .max_height(100.0)
-                    .show(ui, |ui| {
-                        ui.label(
-                            egui::RichText::new(format!("{}", self.error_message.lock().unwrap()))
-                                .color(Color32::LIGHT_RED)
-                                .size(16.0),
-                        );
-                    });
             }
+            job_status_view::error(ui, &job);
         });
     }
 }
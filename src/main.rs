@@ -19,6 +19,7 @@ use crate::app::cores::{
     config::{config_file_default, get_log_path},
     depen_manager::{Depen, get_path, install},
     notify::fail_sound,
+    queue::JobQueue,
     ytdlp,
 };
 use eframe::egui::{self, Ui, global_theme_preference_buttons};
@@ -33,6 +34,11 @@ async fn main() -> eframe::Result {
         .retention_days(7)
         .init();
 
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if app::cli::is_cli(&args) {
+        std::process::exit(app::cli::run(&args));
+    }
+
     let options = eframe::NativeOptions {
         centered: true,
         ..Default::default()
@@ -48,10 +54,17 @@ struct MainApp {
     music_download: app::music_dl::MusicDownload,
     video_download: app::video_dl::VideoDownload,
     ffmpeg_ui: app::ffmpeg::Ffmpeg,
+    lyrics_library: app::lyrics_library::LyricsLibrary,
+    lyrics_editor: app::lyrics_editor::LyricsEditor,
+    tag_editor: app::tag_editor::TagEditor,
+    queue_ui: app::queue_view::QueueView,
+    queue: JobQueue,
     run_on_start: bool,
     yt: bool,
     yt_version: String,
     ffmpeg: bool,
+    lyrics: bool,
+    tags: bool,
     app_data: Depen,
     is_install_depen: Arc<AtomicBool>,
     log_path: PathBuf,
@@ -90,14 +103,22 @@ impl MainApp {
             }
         };
         let log_path = get_log_path();
+        let queue = JobQueue::load(config::get_queue_path(), &configs);
         Self {
             music_download: app::music_dl::MusicDownload::default(),
             video_download: app::video_dl::VideoDownload::default(),
             ffmpeg_ui: app::ffmpeg::Ffmpeg::default(),
+            lyrics_library: app::lyrics_library::LyricsLibrary::default(),
+            lyrics_editor: app::lyrics_editor::LyricsEditor::default(),
+            tag_editor: app::tag_editor::TagEditor::default(),
+            queue_ui: app::queue_view::QueueView::default(),
+            queue,
             run_on_start: false,
             yt_version,
             yt: true,
             ffmpeg: true,
+            lyrics: true,
+            tags: true,
             app_data,
             is_install_depen: Arc::new(AtomicBool::new(false)),
             log_path,
@@ -292,6 +313,11 @@ impl eframe::App for MainApp {
             .is_install_depen
             .load(std::sync::atomic::Ordering::Relaxed)
         {
+            self.queue.tick();
+            if self.queue.is_busy() {
+                ui.ctx()
+                    .request_repaint_after(std::time::Duration::from_millis(500));
+            }
             egui::Panel::left("Panel")
                 .resizable(true)
                 .size_range(50.0..=100.0)
@@ -306,6 +332,16 @@ impl eframe::App for MainApp {
                         egui::RichText::new("ffmpeg").size(17.0),
                     ));
                     ui.separator();
+                    ui.add(egui::Checkbox::new(
+                        &mut self.lyrics,
+                        egui::RichText::new("lyrics").size(17.0),
+                    ));
+                    ui.separator();
+                    ui.add(egui::Checkbox::new(
+                        &mut self.tags,
+                        egui::RichText::new("tags").size(17.0),
+                    ));
+                    ui.separator();
                 });
             if self.faq.is_none() {
                 egui::Window::new("FAQ").auto_sized().default_open(true).anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0]).show(ui, |ui| {
@@ -313,7 +349,7 @@ impl eframe::App for MainApp {
                     ui.label(egui::RichText::new("- There is a Log button in the About tab that will show you all the logs of your usage for debugging purposes.").size(18.0));
                     ui.label(egui::RichText::new("- In a Flatpak environment, Azul Box can only install video and audio into the Downloads, Video, Music directories.").size(18.0));
                     ui.label(egui::RichText::new("- For the music downloader, lyrics (if they exist) will be embedded into your audio file. You will need an audio player that supports this (Elisa is recommended).").size(18.0));
-                    ui.label(egui::RichText::new("- Lyrics sources are ordered as follows: Kugou = 0, lrclib = 1, and YouTube = 2. This means that if you enable all sources and each provides lyrics, the Kugou lyrics will be embedded into your file.").size(18.0));
+                    ui.label(egui::RichText::new("- Lyrics sources are tried in the order set in Lyrics > Source order (Kugou, lrclib, then YouTube by default). The first good match is embedded and the other sources are skipped.").size(18.0));
                     ui.label(egui::RichText::new("- For now, the cookies function for YouTube downloads does NOT work. Please don’t use it (I will fix it in the future).").size(18.0));
                     ui.label(egui::RichText::new("This message can be turned off completely using a button in the About tab.").color(Color32::DARK_GRAY).size(25.0).background_color(Color32::LIGHT_BLUE));
                 });
@@ -324,13 +360,15 @@ impl eframe::App for MainApp {
                     .default_open(false)
                     .default_pos(egui::Pos2::new(100.0, 100.0))
                     .resizable(false)
-                    .show(ui, |ui| self.music_download.ui(ui, &self.app_data));
+                    .show(ui, |ui| {
+                        self.music_download.ui(ui, &self.app_data, &self.queue)
+                    });
                 //Video
                 egui::Window::new("Video-dl")
                     .default_open(false)
                     .resizable(false)
                     .show(ui, |ui| {
-                        self.video_download.ui(ui, &self.app_data);
+                        self.video_download.ui(ui, &self.app_data, &self.queue);
                     });
             }
             if self.ffmpeg {
@@ -338,9 +376,38 @@ impl eframe::App for MainApp {
                     .default_open(false)
                     .resizable(false)
                     .show(ui, |ui| {
-                        self.ffmpeg_ui.ui(ui, &self.app_data);
+                        self.ffmpeg_ui.ui(ui, &self.app_data, &self.queue);
                     });
             }
+            if self.lyrics {
+                egui::Window::new("Library lyrics")
+                    .default_open(false)
+                    .resizable(false)
+                    .show(ui, |ui| {
+                        self.lyrics_library.ui(ui, &self.queue);
+                    });
+                egui::Window::new("Lyrics editor")
+                    .default_open(false)
+                    .resizable(false)
+                    .show(ui, |ui| {
+                        self.lyrics_editor.ui(ui, &self.app_data);
+                    });
+            }
+            if self.tags {
+                egui::Window::new("Tags")
+                    .default_open(false)
+                    .resizable(false)
+                    .show(ui, |ui| {
+                        self.tag_editor.ui(ui);
+                    });
+            }
+            egui::Window::new("Queue")
+                .default_open(false)
+                .resizable(true)
+                .show(ui, |ui| {
+                    self.queue_ui.ui(ui, &self.queue);
+                });
+            self.queue_ui.review(ui, &self.queue);
             egui::CentralPanel::default().show_inside(ui, |ui| ui.label(""));
         } else {
             egui::CentralPanel::default().show_inside(ui, |ui| {